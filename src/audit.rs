@@ -0,0 +1,125 @@
+use std::{fs, io::Write, path::Path};
+use anyhow::{anyhow, Error};
+use crate::types::{AuditEvent, AwsProfiles};
+
+/// The name of the audit log, which is kept next to the config file
+const AUDIT_LOG_FILE_NAME: &str = "audit.jsonl";
+
+/// Returns the path of the audit log that belongs to a config file
+pub fn get_audit_log_path(config_path: &str) -> String {
+    Path::new(config_path).with_file_name(AUDIT_LOG_FILE_NAME).to_string_lossy().to_string()
+}
+
+/// Returns a new event for a profile. The expiration is that of the session the event handed out, if
+/// any. `trailing_command` is the command passed to `exec`, which is left out of the recorded command
+/// line since its arguments may hold secrets.
+pub fn new_event(event: &str, profile_name: &str, expiration: &str, trailing_command: &[String]) -> AuditEvent {
+    AuditEvent {
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        event: event.to_string(),
+        profile: profile_name.to_string(),
+        caller_arn: None,
+        expiration: Some(expiration.to_string()).filter(|e| !e.is_empty()),
+        renamed_to: None,
+        command: strip_trailing_command(std::env::args().collect(), trailing_command),
+    }
+}
+
+/// Appends an event to the audit log, unless `disable_audit_log` is set. The caller identity is taken
+/// from `all_creds` (under the new name for renames), so for removals it must still contain the profile.
+pub fn record_event(all_creds: &AwsProfiles, config_path: &str, mut audit_event: AuditEvent) -> Result<(), Error> {
+    if all_creds.disable_audit_log {
+        return Ok(());
+    }
+
+    let name = audit_event.renamed_to.as_ref().unwrap_or(&audit_event.profile);
+    audit_event.caller_arn = all_creds.profiles
+        .iter()
+        .find(|p| &p.profile_name == name)
+        .and_then(|p| p.caller_arn.to_owned());
+
+    let path = get_audit_log_path(config_path);
+    let mut options = fs::OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    // A single write per line, so that concurrent invocations can't interleave their events
+    let line = format!("{}\n", serde_json::to_string(&audit_event)?);
+    options
+        .open(&path)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .map_err(|e| anyhow!("failed to write to the audit log `{}`: {}", path, e))
+}
+
+/// Drops the trailing command (and the `--` before it) from the command line
+fn strip_trailing_command(mut args: Vec<String>, trailing_command: &[String]) -> Vec<String> {
+    if !trailing_command.is_empty() && args.ends_with(trailing_command) {
+        args.truncate(args.len() - trailing_command.len());
+        if args.last().is_some_and(|a| a == "--") {
+            args.pop();
+        }
+    }
+
+    args
+}
+
+/// Reads the events of the audit log, oldest first. Lines that can't be parsed are skipped with a
+/// warning, so that one damaged line doesn't hide the rest of the log.
+pub fn read_events(config_path: &str) -> Result<Vec<AuditEvent>, Error> {
+    let path = get_audit_log_path(config_path);
+    let contents = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow!("failed to read the audit log `{}`: {}", path, e)),
+    };
+
+    Ok(parse_events(&contents))
+}
+
+fn parse_events(contents: &str) -> Vec<AuditEvent> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .filter_map(|(i, l)| match serde_json::from_str(l) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                eprintln!("WARNING: skipping line {} of the audit log: {}", i + 1, e);
+                None
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_strip_trailing_command() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        let command = args(&["mysql", "-pSECRET"]);
+
+        assert_eq!(strip_trailing_command(args(&["aws-creds", "exec", "-p", "prod", "--", "mysql", "-pSECRET"]), &command), args(&["aws-creds", "exec", "-p", "prod"]));
+        assert_eq!(strip_trailing_command(args(&["aws-creds", "exec", "mysql", "-pSECRET"]), &command), args(&["aws-creds", "exec"]));
+        assert_eq!(strip_trailing_command(args(&["aws-creds", "show", "--", "prod"]), &[]), args(&["aws-creds", "show", "--", "prod"]));
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let contents = r#"{"timestamp":"2025-01-01T12:00:00Z","event":"get","profile":"prod","caller_arn":"arn:aws:iam::123456789012:user/alice","expiration":"2025-01-02T00:00:00Z","command":["aws-creds","get"]}
+not json
+
+{"timestamp":"2025-01-01T12:05:00Z","event":"show","profile":"prod","command":["aws-creds","show"]}"#;
+
+        let events = parse_events(contents);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].caller_arn.as_deref(), Some("arn:aws:iam::123456789012:user/alice"));
+        assert_eq!(events[1].event, "show");
+        assert_eq!(events[1].expiration, None);
+    }
+}
\ No newline at end of file
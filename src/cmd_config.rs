@@ -0,0 +1,15 @@
+use crate::utils::resolve_config_path;
+
+/// Prints the config path that would be used, along with where it came from
+pub fn show_config_path(config_path: &Option<String>) -> Result<(), anyhow::Error> {
+    let (path, source) = resolve_config_path(config_path)?;
+
+    println!("{}", path);
+    if std::path::Path::new(&path).exists() {
+        eprintln!("(from {})", source);
+    } else {
+        eprintln!("(from {}; the file doesn't exist yet)", source);
+    }
+
+    Ok(())
+}
\ No newline at end of file
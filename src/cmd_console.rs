@@ -0,0 +1,181 @@
+use std::process::Command;
+use anyhow::{anyhow, Error};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use crate::{
+    cmd_get::create_sts_client,
+    cmd_show::load_temp_creds,
+    types::{CredentialsProfile, TemporaryCredentials},
+    utils::{check_config_path, get_project_region, parse_creds, resolve_profile_name},
+};
+
+/// Allows everything, which `GetFederationToken` intersects with the IAM user's own permissions
+const FEDERATION_POLICY: &str = r#"{"Version":"2012-10-17","Statement":[{"Effect":"Allow","Action":"*","Resource":"*"}]}"#;
+
+/// The longest name `GetFederationToken` accepts
+const MAX_FEDERATED_USER_NAME_LENGTH: usize = 32;
+
+#[derive(Deserialize)]
+struct SigninTokenResponse {
+    #[serde(rename = "SigninToken")]
+    signin_token: String,
+}
+
+/// Prints (or opens) a URL that signs into the AWS console with the profile's credentials. Role
+/// sessions are exchanged directly, while profiles holding `GetSessionToken` sessions, which the
+/// federation endpoint rejects, get a federation token from their permanent credentials instead.
+pub fn open_console(
+    profile_name: &Option<String>,
+    destination: &Option<String>,
+    open: bool,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let mut profile = match all_creds.profiles.into_iter().find(|p| p.profile_name == name) {
+        Some(p) => p,
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    if let Some(region) = get_project_region(&name)? {
+        profile.permanent_credentials.region = region;
+    }
+
+    // Loading the credentials may refresh them, which starts a runtime of its own, so it's done first
+    let session = match profile.web_identity {
+        Some(_) => Some(load_temp_creds(&Some(name.to_owned()), config_path, &None, false, false)?.1),
+        None => None,
+    };
+
+    let region = &profile.permanent_credentials.region;
+    let signin_url = format!("https://{}/federation", get_signin_domain(region));
+    let signin_token = get_signin_token(&signin_url, &profile, session)?;
+
+    let url = build_login_url(&signin_url, &get_destination(destination, region), &signin_token);
+    if !open {
+        println!("{}", url);
+        return Ok(());
+    }
+
+    let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+    Command::new(opener)
+        .arg(&url)
+        .status()
+        .map_err(|e| anyhow!("failed to run `{}`: {}", opener, e))?;
+
+    Ok(())
+}
+
+/// Exchanges a session for a sign-in token. Without a session, a federation token is retrieved first.
+#[::tokio::main]
+async fn get_signin_token(signin_url: &str, profile: &CredentialsProfile, session: Option<TemporaryCredentials>) -> Result<String, Error> {
+    let session = match session {
+        Some(s) => s,
+        None => get_federation_token(profile).await?,
+    };
+
+    let session_json = serde_json::json!({
+        "sessionId": session.access_key_id,
+        "sessionKey": session.secret_access_key,
+        "sessionToken": session.session_token,
+    })
+    .to_string();
+
+    let response = reqwest::get(format!("{}?Action=getSigninToken&Session={}", signin_url, encode(&session_json)))
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| anyhow!("failed to get sign-in token: {}", e))?
+        .text()
+        .await
+        .map_err(|e| anyhow!("failed to read sign-in token: {}", e))?;
+
+    Ok(
+        serde_json::from_str::<SigninTokenResponse>(&response)
+            .map_err(|e| anyhow!("failed to parse sign-in token: {}", e))?
+            .signin_token
+    )
+}
+
+/// Retrieves a federation token with the permanent credentials. The console session then lacks
+/// MFA context, since `GetFederationToken` doesn't accept an MFA code.
+async fn get_federation_token(profile: &CredentialsProfile) -> Result<TemporaryCredentials, Error> {
+    let name: String = format!("aws-creds-{}", profile.profile_name)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "+=,.@-_".contains(*c))
+        .take(MAX_FEDERATED_USER_NAME_LENGTH)
+        .collect();
+
+    let creds = create_sts_client(&profile.permanent_credentials)?
+        .get_federation_token()
+        .name(name)
+        .policy(FEDERATION_POLICY)
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to get federation token: {}", e))?
+        .credentials
+        .ok_or_else(|| anyhow!("no credentials returned in response"))?;
+
+    Ok(TemporaryCredentials {
+        access_key_id: creds.access_key_id().to_string(),
+        secret_access_key: creds.secret_access_key().to_string(),
+        session_token: creds.session_token().to_string(),
+        expiration: creds.expiration().to_string(),
+    })
+}
+
+/// Returns the sign-in domain of the region's partition
+fn get_signin_domain(region: &str) -> &'static str {
+    if region.starts_with("us-gov-") {
+        "signin.amazonaws-us-gov.com"
+    } else if region.starts_with("cn-") {
+        "signin.amazonaws.cn"
+    } else {
+        "signin.aws.amazon.com"
+    }
+}
+
+/// Returns the console URL to land on. Destinations that aren't URLs are treated as a console path
+/// (e.g. `s3` or `ec2/home`) in the profile's region.
+fn get_destination(destination: &Option<String>, region: &str) -> String {
+    let console = get_signin_domain(region).replacen("signin", "console", 1);
+    match destination {
+        Some(d) if d.starts_with("https://") => d.to_owned(),
+        Some(d) => format!("https://{}/{}?region={}", console, d.trim_matches('/'), region),
+        None => format!("https://{}/console/home?region={}", console, region),
+    }
+}
+
+fn build_login_url(signin_url: &str, destination: &str, signin_token: &str) -> String {
+    format!(
+        "{}?Action=login&Issuer=aws-creds&Destination={}&SigninToken={}",
+        signin_url,
+        encode(destination),
+        encode(signin_token),
+    )
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_destination() {
+        assert_eq!(get_destination(&None, "eu-west-1"), "https://console.aws.amazon.com/console/home?region=eu-west-1");
+        assert_eq!(get_destination(&Some("s3/".to_string()), "us-east-1"), "https://console.aws.amazon.com/s3?region=us-east-1");
+        assert_eq!(get_destination(&None, "us-gov-west-1"), "https://console.amazonaws-us-gov.com/console/home?region=us-gov-west-1");
+        assert_eq!(get_destination(&Some("https://example.com/".to_string()), "us-east-1"), "https://example.com/");
+    }
+
+    #[test]
+    fn test_build_login_url() {
+        assert_eq!(
+            build_login_url("https://signin.aws.amazon.com/federation", "https://console.aws.amazon.com/", "a+b"),
+            "https://signin.aws.amazon.com/federation?Action=login&Issuer=aws-creds&Destination=https%3A%2F%2Fconsole%2Eaws%2Eamazon%2Ecom%2F&SigninToken=a%2Bb",
+        );
+    }
+}
\ No newline at end of file
@@ -0,0 +1,202 @@
+use std::{fs, io::Read, path::Path};
+use anyhow::{anyhow, Error};
+use aws_config::Region;
+use aws_sdk_ecr::{config::Credentials, Config};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crate::{
+    cmd_show::load_temp_creds,
+    types::{EcrToken, EcrTokenCache, TemporaryCredentials},
+    utils::{check_config_path, parse_creds, resolve_profile_name, write_private_file},
+};
+
+/// The name of the token cache, which is kept next to the config file
+const CACHE_FILE_NAME: &str = "ecr-tokens.json";
+
+/// Cached tokens are replaced this long before they expire, so that a pull can't outlive its token
+const EXPIRATION_MARGIN_MINUTES: i64 = 5;
+
+/// What Docker expects on stdout when a helper has no credentials for a registry
+const NOT_FOUND_MESSAGE: &str = "credentials not found in native keychain";
+
+/// Implements Docker's credential helper protocol for ECR registries. `get` prints a cached ECR
+/// authorization token, or retrieves a new one with the registry's profile, `list` prints the known
+/// registries, and `erase` drops a registry's cached token. Nothing is stored, so `store` does nothing.
+pub fn docker_credential(operation: &str, config_path: &Option<String>) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let cache_path = Path::new(&path).with_file_name(CACHE_FILE_NAME).to_string_lossy().to_string();
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).map_err(|e| anyhow!("failed to read from Docker: {}", e))?;
+
+    match operation {
+        "get" => {
+            let server_url = input.trim();
+            let registry = get_registry_host(server_url);
+            let region = match get_ecr_region(registry) {
+                Some(r) => r,
+                None => {
+                    println!("{}", NOT_FOUND_MESSAGE);
+                    std::process::exit(1);
+                },
+            };
+
+            let all_creds = parse_creds(&path)?;
+            let name = match all_creds.docker_registries.get(registry) {
+                Some(p) => p.to_owned(),
+                None => resolve_profile_name(&None, &all_creds)?,
+            };
+
+            let mut cache = read_cache(&cache_path);
+            let now = chrono::Utc::now();
+            let password = match cache.tokens.get(registry) {
+                Some(t) if t.profile_name == name && is_token_valid(t, now) => t.password.to_owned(),
+                _ => {
+                    // Docker reads the answer from stdout, so there's no way to prompt for a refresh
+                    let (_, temp_creds) = load_temp_creds(&Some(name.to_owned()), config_path, &Some("0s".to_string()), true, false)?;
+                    let token = get_authorization_token(&temp_creds, region, &name)?;
+                    let password = token.password.to_owned();
+                    cache.tokens.insert(registry.to_string(), token);
+                    write_private_file(&cache_path, &serde_json::to_string(&cache)?)?;
+                    password
+                },
+            };
+
+            let credentials = serde_json::json!({
+                "ServerURL": server_url,
+                "Username": "AWS",
+                "Secret": password,
+            });
+
+            println!("{}", credentials);
+        },
+        "list" => {
+            let all_creds = parse_creds(&path)?;
+            let mut registries = serde_json::Map::new();
+            for registry in all_creds.docker_registries.keys().chain(read_cache(&cache_path).tokens.keys()) {
+                registries.insert(registry.to_owned(), serde_json::Value::from("AWS"));
+            }
+
+            println!("{}", serde_json::Value::Object(registries));
+        },
+        "erase" => {
+            let mut cache = read_cache(&cache_path);
+            if cache.tokens.remove(get_registry_host(input.trim())).is_some() {
+                write_private_file(&cache_path, &serde_json::to_string(&cache)?)?;
+            }
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+/// Reads the token cache. A missing or unreadable cache is treated as empty, since it can always be
+/// rebuilt.
+fn read_cache(cache_path: &str) -> EcrTokenCache {
+    fs::read_to_string(cache_path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn is_token_valid(token: &EcrToken, now: chrono::DateTime<chrono::Utc>) -> bool {
+    match chrono::DateTime::parse_from_rfc3339(&token.expiration) {
+        Ok(e) => e.with_timezone(&chrono::Utc) > now + chrono::Duration::minutes(EXPIRATION_MARGIN_MINUTES),
+        Err(_) => false,
+    }
+}
+
+/// Retrieves an ECR authorization token. The token stops working when the session it was retrieved
+/// with expires, so it's cached until whichever expires first.
+#[::tokio::main]
+async fn get_authorization_token(temp_creds: &TemporaryCredentials, region: &str, profile_name: &str) -> Result<EcrToken, Error> {
+    let creds = Credentials::new(
+        &temp_creds.access_key_id,
+        &temp_creds.secret_access_key,
+        Some(temp_creds.session_token.to_owned()),
+        None,
+        "",
+    );
+
+    let conf = Config::builder()
+        .region(Region::new(region.to_owned()))
+        .credentials_provider(creds)
+        .behavior_version_latest()
+        .build();
+
+    let response = aws_sdk_ecr::Client::from_conf(conf)
+        .get_authorization_token()
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to get ECR authorization token: {}", e))?;
+
+    let data = response
+        .authorization_data()
+        .first()
+        .ok_or_else(|| anyhow!("no authorization data returned in response"))?;
+
+    // The token is `AWS:<password>`, base64 encoded
+    let token = data.authorization_token().ok_or_else(|| anyhow!("no authorization token returned in response"))?;
+    let decoded = String::from_utf8(STANDARD.decode(token)?)?;
+    let password = decoded.strip_prefix("AWS:").ok_or_else(|| anyhow!("the ECR authorization token is invalid"))?;
+
+    let mut expiration = data.expires_at().map(|e| e.to_string()).unwrap_or_default();
+    if let (Ok(e), Ok(s)) = (chrono::DateTime::parse_from_rfc3339(&expiration), chrono::DateTime::parse_from_rfc3339(&temp_creds.expiration)) {
+        expiration = e.min(s).to_rfc3339();
+    }
+
+    Ok(EcrToken {
+        profile_name: profile_name.to_string(),
+        password: password.to_string(),
+        expiration,
+    })
+}
+
+/// Returns the host of a registry's server URL (e.g. `https://123456789012.dkr.ecr.eu-west-1.amazonaws.com/v2/`)
+fn get_registry_host(server_url: &str) -> &str {
+    let url = server_url.split_once("://").map(|(_, u)| u).unwrap_or(server_url);
+    url.split('/').next().unwrap_or(url)
+}
+
+/// Returns the region of a private ECR registry (e.g. `123456789012.dkr.ecr.eu-west-1.amazonaws.com`)
+fn get_ecr_region(registry: &str) -> Option<&str> {
+    let labels: Vec<&str> = registry.split('.').collect();
+    match labels.as_slice() {
+        [_, "dkr", "ecr" | "ecr-fips", region, "amazonaws", "com"] => Some(region),
+        [_, "dkr", "ecr", region, "amazonaws", "com", "cn"] => Some(region),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_registry_host() {
+        assert_eq!(get_registry_host("https://123456789012.dkr.ecr.eu-west-1.amazonaws.com/v2/"), "123456789012.dkr.ecr.eu-west-1.amazonaws.com");
+        assert_eq!(get_registry_host("123456789012.dkr.ecr.eu-west-1.amazonaws.com"), "123456789012.dkr.ecr.eu-west-1.amazonaws.com");
+    }
+
+    #[test]
+    fn test_get_ecr_region() {
+        assert_eq!(get_ecr_region("123456789012.dkr.ecr.eu-west-1.amazonaws.com"), Some("eu-west-1"));
+        assert_eq!(get_ecr_region("123456789012.dkr.ecr-fips.us-east-1.amazonaws.com"), Some("us-east-1"));
+        assert_eq!(get_ecr_region("123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn"), Some("cn-north-1"));
+        assert_eq!(get_ecr_region("public.ecr.aws"), None);
+        assert_eq!(get_ecr_region("docker.io"), None);
+    }
+
+    #[test]
+    fn test_is_token_valid() {
+        let now = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let token = |expiration: &str| EcrToken {
+            profile_name: "test".to_string(),
+            password: "password".to_string(),
+            expiration: expiration.to_string(),
+        };
+
+        assert!(is_token_valid(&token("2025-01-01T18:00:00Z"), now));
+        assert!(!is_token_valid(&token("2025-01-01T12:04:00Z"), now));
+        assert!(!is_token_valid(&token(""), now));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::process::Command;
+use anyhow::{anyhow, Error};
+use crate::{
+    cmd_show::{get_env_vars, get_extra_env_vars, load_temp_creds, record_export},
+    shell::{get_stale_vars, ExtraVar},
+};
+
+/// Runs a command with the temporary credentials of a profile and the selected extra variables set as
+/// environment variables, then exits with the command's exit code
+pub fn exec_command(
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+    min_lifetime: &Option<String>,
+    strict: bool,
+    passthrough: bool,
+    extra: &[ExtraVar],
+    command: &[String],
+) -> Result<(), Error> {
+    let (name, profile_creds) = load_temp_creds(profile_name, config_path, min_lifetime, strict, passthrough)?;
+    record_export(config_path, "exec", &name, &profile_creds, command)?;
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("no command was passed"))?;
+
+    let mut vars = get_env_vars(&profile_creds);
+    vars.extend(get_extra_env_vars(&name, &profile_creds, extra, config_path)?);
+
+    let status = build_command(program, args, &vars, extra)
+        .status()
+        .map_err(|e| anyhow!("failed to run `{}`: {}", program, e))?;
+
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// Builds the command with the variables set, and without what's left over from an earlier profile
+/// in this environment, so that e.g. passed through credentials aren't paired with an old session token
+fn build_command(program: &str, args: &[String], vars: &[(&str, String)], extra: &[ExtraVar]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args).envs(vars.iter().map(|(k, v)| (k, v)));
+    for name in get_stale_vars(vars, extra) {
+        command.env_remove(name);
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_build_command() {
+        let vars = vec![
+            ("AWS_ACCESS_KEY_ID", "AKIAEXAMPLE".to_string()),
+            ("AWS_SECRET_ACCESS_KEY", "secret".to_string()),
+            ("AWS_CREDS_PROFILE", "prod".to_string()),
+        ];
+
+        let command = build_command("aws", &["s3".to_string(), "ls".to_string()], &vars, &[ExtraVar::Expiration, ExtraVar::Profile]);
+        let envs: Vec<(&OsStr, Option<&OsStr>)> = command.get_envs().collect();
+
+        assert!(envs.contains(&(OsStr::new("AWS_ACCESS_KEY_ID"), Some(OsStr::new("AKIAEXAMPLE")))));
+        assert!(envs.contains(&(OsStr::new("AWS_SESSION_TOKEN"), None)));
+        assert!(envs.contains(&(OsStr::new("AWS_CREDENTIAL_EXPIRATION"), None)));
+
+        // The region wasn't selected, so the one in the environment is kept
+        assert!(!envs.iter().any(|(k, _)| *k == OsStr::new("AWS_REGION")));
+    }
+}
\ No newline at end of file
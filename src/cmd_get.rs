@@ -1,48 +1,212 @@
+use std::{fs, process::Command};
 use aws_config::Region;
 use aws_sdk_sts::{config::Credentials, Config};
 use inquire::{self, validator::Validation};
 use anyhow::{Error, anyhow};
 use crate::{
-    types::{AwsProfiles, PermanentCredentials, TemporaryCredentials},
-    utils::{check_config_path, parse_creds, parse_profile_name, write_creds},
+    audit,
+    totp,
+    types::{AwsProfiles, MfaDevice, PermanentCredentials, TemporaryCredentials, WebIdentity},
+    utils::{
+        check_config_path, get_project_region, group_profiles_by_user, mark_profile_used, parse_creds, parse_profile_name,
+        resolve_profile_name, select_profiles_by_tags, write_creds,
+    },
 };
 
+/// Retrieves new temporary credentials for the given profiles and the profiles with every one of the
+/// tags (or the default profile if neither are passed). Profiles are grouped by access key, i.e. by IAM
+/// user, so that a single MFA code retrieves one session, which is then stored in every profile of the
+/// group. The code may come from any MFA device listed by a profile of the group.
+/// Web identity profiles assume their role with an OIDC token instead.
 #[::tokio::main]
-pub async fn get_new_creds(profile_name: &Option<String>, config_path: &Option<String>) -> Result<(), Error> {
+pub async fn get_new_creds(
+    profile_names: &[String],
+    tags: &[String],
+    all: bool,
+    mfa_device: &Option<String>,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
 
     let path = check_config_path(config_path)?;
     let mut all_creds = parse_creds(&path)?;
-    let name = parse_profile_name(profile_name, &all_creds.default)?;
 
-    // Copy perm_creds to appease the borrow checker gods
-    let perm_creds = {
-        let profile =  match all_creds.profiles.iter().find(|p| p.profile_name == name) {
-            Some(p) => p,
+    let mut names: Vec<String> = Vec::new();
+    if all {
+        names = all_creds.profiles.iter().map(|p| p.profile_name.to_owned()).collect();
+    } else if profile_names.is_empty() && tags.is_empty() {
+        names.push(resolve_profile_name(&None, &all_creds)?);
+    } else {
+        for n in profile_names {
+            let name = parse_profile_name(&Some(n.to_owned()), &all_creds.default)?;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        if !tags.is_empty() {
+            for name in select_profiles_by_tags(&all_creds, tags)? {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    // Copying perm_creds to appease the borrow checker gods
+    let mut profiles: Vec<(String, PermanentCredentials)> = Vec::new();
+    let mut web_identities: Vec<(String, WebIdentity, String)> = Vec::new();
+    for name in names {
+        let (mut perm_creds, web_identity) = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+            Some(p) => (p.permanent_credentials.clone(), p.web_identity.clone()),
             None => return Err(anyhow::anyhow!("profile `{}` doesn't exist", name)),
         };
 
-            profile.permanent_credentials.clone()
-    };
+        if let Some(region) = get_project_region(&name)? {
+            perm_creds.region = region;
+        }
+
+        // Web identity profiles don't need an MFA code, so they're retrieved on their own
+        if let Some(w) = web_identity {
+            web_identities.push((name, w, perm_creds.region));
+            continue;
+        }
+
+        profiles.push((name, perm_creds));
+    }
+
+    let groups = group_profiles_by_user(profiles);
+
+    let multiple = groups.len() + web_identities.len() > 1 || groups.iter().any(|(_, n)| n.len() > 1);
+    for (name, web_identity, region) in web_identities {
+        let (temp_creds, caller_arn) = assume_role_with_web_identity(&web_identity, &region).await?;
+        let expiration = temp_creds.expiration().to_string();
+        update_credentials(&mut all_creds, temp_creds, caller_arn, name.to_owned());
+        mark_profile_used(&mut all_creds, &name);
+        write_creds(&all_creds, &path)?;
+        audit::record_event(&all_creds, &path, audit::new_event("get", &name, &expiration, &[]))?;
+
+        if multiple {
+            eprintln!("Retrieved temporary credentials for {}", name);
+        }
+    }
+
+    for (perm_creds, group_names) in groups {
+        let prompt = if multiple {
+            format!("MFA Code for {}:", group_names.iter().map(|n| format!("`{}`", n)).collect::<Vec<String>>().join(", "))
+        } else {
+            "MFA Code:".to_string()
+        };
+
+        let device = choose_mfa_device(&perm_creds, mfa_device, &group_names)?;
+        let mfa = match &device {
+            Some(d) => {
+                let code = match &d.totp_seed {
+                    Some(seed) => totp::generate_current_code(seed)?,
+                    None => inquire::Text::new(&prompt)
+                        .with_validator(|input: &str| {
+                            if input.trim().is_empty() {
+                                Ok(Validation::Invalid("field cannot be empty".into()))
+                            } else {
+                                Ok(Validation::Valid)
+                            }
+                        })
+                        .prompt()
+                        .map_err(|_| anyhow!("failed to retrieve MFA Code"))?,
+                };
+
+                Some((d.serial_number.to_owned(), code))
+            },
+            None => None,
+        };
+
+        let sts_client = create_sts_client(&perm_creds)?;
+        let temp_creds = get_temporary_credentials(&sts_client, &mfa).await?;
 
-    let session_token= inquire::Text::new("MFA Code:")
-        .with_validator(|input: &str| {
-            if input.trim().is_empty() {
-                Ok(Validation::Invalid("field cannot be empty".into()))
-            } else {
-                Ok(Validation::Valid)
+        // The identity of an access key never changes, so it's only looked up once. The audit log
+        // can do without it, so failing to look it up isn't an error.
+        let known_arn = all_creds.profiles
+            .iter()
+            .filter(|p| group_names.contains(&p.profile_name))
+            .find_map(|p| p.caller_arn.to_owned());
+
+        let caller_arn = match known_arn {
+            Some(a) => Some(a),
+            None => sts_client
+                .get_caller_identity()
+                .send()
+                .await
+                .ok()
+                .and_then(|i| i.arn().map(|a| a.to_string())),
+        };
+
+        for name in group_names.iter() {
+            update_credentials(&mut all_creds, temp_creds.to_owned(), caller_arn.to_owned(), name.to_owned());
+            mark_profile_used(&mut all_creds, name);
+        }
+
+        // Remember the device for the profiles that list it, so that it's preselected next time
+        if let Some(d) = &device {
+            for p in all_creds.profiles.iter_mut().filter(|p| group_names.contains(&p.profile_name)) {
+                if p.permanent_credentials.mfa_devices.contains(d) {
+                    p.permanent_credentials.last_mfa_device = Some(d.label.to_owned());
+                }
             }
-        })
-        .prompt()
-        .map_err(|_| anyhow!("failed to retrieve MFA Code"))?;
+        }
 
-    let sts_client = create_sts_client(&perm_creds)?;
-    let temp_creds = get_temporary_credentials(&sts_client, &perm_creds, &session_token).await?;
+        // Save after every group so that a failure later on doesn't discard the sessions already retrieved
+        write_creds(&all_creds, &path)?;
+        for name in group_names.iter() {
+            audit::record_event(&all_creds, &path, audit::new_event("get", name, &temp_creds.expiration().to_string(), &[]))?;
+        }
 
-    update_credentials(&mut all_creds, temp_creds, name);
-    Ok(write_creds(&all_creds, &path)?)
+        if multiple {
+            eprintln!("Retrieved temporary credentials for {}", group_names.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the MFA device matching `requested` (by label or serial number). Otherwise, when there is
+/// more than one device, the user is asked to choose, starting from the device that was used last.
+/// Profiles without MFA devices (e.g. CI service accounts) return `None`.
+fn choose_mfa_device(
+    perm_creds: &PermanentCredentials,
+    requested: &Option<String>,
+    profile_names: &[String],
+) -> Result<Option<MfaDevice>, Error> {
+    let devices = &perm_creds.mfa_devices;
+    if let Some(r) = requested {
+        return match devices.iter().find(|d| &d.label == r || &d.serial_number == r) {
+            Some(d) => Ok(Some(d.to_owned())),
+            None => Err(anyhow!(
+                "MFA device `{}` doesn't exist for profile `{}` (available devices: {})",
+                r,
+                profile_names.join("`, `"),
+                devices.iter().map(|d| d.label.to_owned()).collect::<Vec<String>>().join(", "),
+            )),
+        };
+    }
+
+    if devices.len() <= 1 {
+        return Ok(devices.first().cloned());
+    }
+
+    let starting_cursor = devices
+        .iter()
+        .position(|d| Some(&d.label) == perm_creds.last_mfa_device.as_ref())
+        .unwrap_or(0);
+
+    let options: Vec<String> = devices.iter().map(|d| format!("{} ({})", d.label, d.serial_number)).collect();
+    let selection = inquire::Select::new("MFA Device:", options)
+        .with_starting_cursor(starting_cursor)
+        .raw_prompt()
+        .map_err(|_| anyhow!("failed to get user selection for MFA device"))?;
+
+    Ok(Some(devices[selection.index].to_owned()))
 }
 
-fn create_sts_client(perm_creds: &PermanentCredentials) -> Result<aws_sdk_sts::Client, Error> {
+pub fn create_sts_client(perm_creds: &PermanentCredentials) -> Result<aws_sdk_sts::Client, Error> {
      let creds = Credentials::new(
         &perm_creds.access_key_id,
         &perm_creds.secret_access_key,
@@ -60,18 +224,20 @@ fn create_sts_client(perm_creds: &PermanentCredentials) -> Result<aws_sdk_sts::C
   Ok(aws_sdk_sts::Client::from_conf(conf))
 }
 
+/// Calls GetSessionToken, authenticated with the serial number and code of an MFA device if one is passed
 async fn get_temporary_credentials(
     sts_client: &aws_sdk_sts::Client,
-    perm_creds: &PermanentCredentials,
-    session_token: &str,
+    mfa: &Option<(String, String)>,
 ) -> Result<aws_sdk_sts::types::Credentials, Error> {
-    let token_result = sts_client
-    .get_session_token()
-    .serial_number(&perm_creds.mfa_serial_number)
-    .token_code(session_token)
+    let mut request = sts_client.get_session_token();
+    if let Some((mfa_serial_number, session_token)) = mfa {
+        request = request.serial_number(mfa_serial_number).token_code(session_token);
+    }
+
+    let token_result = request
     .send()
     .await
-    .map_err(|e| anyhow!("failed to get session token: {}", e.to_string()))?;
+    .map_err(|e| anyhow!("failed to get session token: {}", e))?;
 
     token_result
         .credentials()
@@ -79,9 +245,71 @@ async fn get_temporary_credentials(
         .cloned()
 }
 
+/// Exchanges the profile's OIDC token for temporary credentials and the ARN of the assumed role's
+/// session. The request isn't signed, so no AWS credentials are needed.
+async fn assume_role_with_web_identity(
+    web_identity: &WebIdentity,
+    region: &str,
+) -> Result<(aws_sdk_sts::types::Credentials, Option<String>), Error> {
+    let token = read_web_identity_token(web_identity)?;
+    let conf = Config::builder()
+        .region(Region::new(region.to_owned()))
+        .behavior_version_latest()
+        .build();
+
+    let response = aws_sdk_sts::Client::from_conf(conf)
+        .assume_role_with_web_identity()
+        .role_arn(&web_identity.role_arn)
+        .role_session_name(&web_identity.role_session_name)
+        .web_identity_token(token)
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to assume role `{}` with web identity: {}", web_identity.role_arn, e))?;
+
+    let credentials = response
+        .credentials()
+        .ok_or_else(|| anyhow!("no credentials returned in response"))?
+        .to_owned();
+
+    Ok((credentials, response.assumed_role_user().map(|u| u.arn().to_string())))
+}
+
+/// Reads the OIDC token from the profile's token file, or from the output of its token command
+fn read_web_identity_token(web_identity: &WebIdentity) -> Result<String, Error> {
+    let token = match (&web_identity.token_file, &web_identity.token_command) {
+        (Some(file), None) => fs::read_to_string(file)
+            .map_err(|e| anyhow!("failed to read web identity token from `{}`: {}", file, e))?,
+        (None, Some(command)) => {
+            let output = Command::new("sh")
+                .args(["-c", command])
+                .output()
+                .map_err(|e| anyhow!("failed to run `{}`: {}", command, e))?;
+
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "the web identity token command `{}` failed: {}",
+                    command,
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                ));
+            }
+
+            String::from_utf8(output.stdout)
+                .map_err(|_| anyhow!("the web identity token command `{}` printed invalid UTF-8", command))?
+        },
+        _ => return Err(anyhow!("web identity profiles need exactly one of `token_file` and `token_command`")),
+    };
+
+    if token.trim().is_empty() {
+        return Err(anyhow!("the web identity token is empty"));
+    }
+
+    Ok(token.trim().to_string())
+}
+
 fn update_credentials(
     all_creds: &mut AwsProfiles,
     aws_creds: aws_sdk_sts::types::Credentials,
+    caller_arn: Option<String>,
     profile_name: String,
 
 ) {
@@ -95,6 +323,9 @@ fn update_credentials(
     for p in all_creds.profiles.iter_mut() {
         if p.profile_name == profile_name {
             p.temporary_credentials = temporary_credentials.to_owned();
+            if caller_arn.is_some() {
+                p.caller_arn = caller_arn.to_owned();
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Error};
+use crate::{
+    audit::read_events,
+    types::AuditEvent,
+    utils::{check_config_path, parse_duration},
+};
+
+/// Prints the events of the audit log that match the filters, oldest first. `since` is a duration
+/// (e.g. `7d`) counted back from now, and `limit` keeps only the most recent events.
+pub fn show_history(
+    profile_name: &Option<String>,
+    events: &[String],
+    since: &Option<String>,
+    limit: &Option<usize>,
+    json: bool,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let since = match since {
+        Some(s) => Some(
+            chrono::Utc::now()
+                .checked_sub_signed(parse_duration(s)?)
+                .ok_or_else(|| anyhow!("the duration `{}` reaches too far back", s))?,
+        ),
+        None => None,
+    };
+
+    let mut matching = filter_events(read_events(&path)?, profile_name, events, since);
+    if let Some(l) = limit {
+        matching.drain(..matching.len().saturating_sub(*l));
+    }
+
+    for event in matching {
+        if json {
+            println!("{}", serde_json::to_string(&event)?);
+        } else {
+            println!("{}", format_event(&event));
+        }
+    }
+
+    Ok(())
+}
+
+fn filter_events(
+    all_events: Vec<AuditEvent>,
+    profile_name: &Option<String>,
+    events: &[String],
+    since: Option<chrono::DateTime<chrono::Utc>>,
+) -> Vec<AuditEvent> {
+    all_events
+        .into_iter()
+        .filter(|e| profile_name.as_ref().is_none_or(|p| &e.profile == p || e.renamed_to.as_ref() == Some(p)))
+        .filter(|e| events.is_empty() || events.contains(&e.event))
+        .filter(|e| match since {
+            Some(s) => chrono::DateTime::parse_from_rfc3339(&e.timestamp).is_ok_and(|t| t >= s),
+            None => true,
+        })
+        .collect()
+}
+
+fn format_event(event: &AuditEvent) -> String {
+    let mut line = format!("{}  {:<6}  {}", event.timestamp, event.event, event.profile);
+    if let Some(new_name) = &event.renamed_to {
+        line.push_str(&format!(" -> {}", new_name));
+    }
+
+    if let Some(arn) = &event.caller_arn {
+        line.push_str(&format!("  {}", arn));
+    }
+
+    if let Some(expiration) = &event.expiration {
+        line.push_str(&format!("  (expires {})", expiration));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filter_events() {
+        let event = |timestamp: &str, event: &str, profile: &str| AuditEvent {
+            timestamp: timestamp.to_string(),
+            event: event.to_string(),
+            profile: profile.to_string(),
+            caller_arn: None,
+            expiration: None,
+            renamed_to: None,
+            command: vec!["aws-creds".to_string(), event.to_string()],
+        };
+
+        let mut rename = event("2025-01-04T12:00:00Z", "rename", "staging");
+        rename.renamed_to = Some("stage".to_string());
+
+        let all_events = vec![
+            event("2025-01-01T12:00:00Z", "get", "prod"),
+            event("2025-01-02T12:00:00Z", "show", "prod"),
+            event("2025-01-03T12:00:00Z", "show", "staging"),
+            rename,
+        ];
+
+        let since = chrono::DateTime::parse_from_rfc3339("2025-01-02T00:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let timestamps = |events: Vec<AuditEvent>| events.into_iter().map(|e| e.timestamp).collect::<Vec<String>>();
+
+        assert_eq!(filter_events(all_events.clone(), &None, &[], None).len(), 4);
+        assert_eq!(timestamps(filter_events(all_events.clone(), &Some("stage".to_string()), &[], None)), vec!["2025-01-04T12:00:00Z"]);
+        assert_eq!(timestamps(filter_events(all_events.clone(), &Some("staging".to_string()), &[], None)), vec!["2025-01-03T12:00:00Z", "2025-01-04T12:00:00Z"]);
+        assert_eq!(timestamps(filter_events(all_events.clone(), &Some("prod".to_string()), &[], None)), vec!["2025-01-01T12:00:00Z", "2025-01-02T12:00:00Z"]);
+        assert_eq!(timestamps(filter_events(all_events.clone(), &None, &["show".to_string()], Some(since))), vec!["2025-01-02T12:00:00Z", "2025-01-03T12:00:00Z"]);
+        assert_eq!(timestamps(filter_events(all_events, &Some("staging".to_string()), &["get".to_string()], None)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_format_event() {
+        let event = AuditEvent {
+            timestamp: "2025-01-01T12:00:00Z".to_string(),
+            event: "get".to_string(),
+            profile: "prod".to_string(),
+            caller_arn: Some("arn:aws:iam::123456789012:user/alice".to_string()),
+            expiration: Some("2025-01-02T00:00:00Z".to_string()),
+            renamed_to: None,
+            command: vec!["aws-creds".to_string(), "get".to_string()],
+        };
+
+        assert_eq!(format_event(&event), "2025-01-01T12:00:00Z  get     prod  arn:aws:iam::123456789012:user/alice  (expires 2025-01-02T00:00:00Z)");
+    }
+}
\ No newline at end of file
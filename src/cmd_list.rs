@@ -1,29 +1,48 @@
-use crate::utils::{check_config_path, get_temp_cred_status, parse_creds, TempCredStatus};
+use crate::utils::{
+    check_config_path, get_refresh_threshold, get_temp_cred_status, is_access_key_overdue, parse_creds,
+    select_profiles_by_tags, TempCredStatus,
+};
 
-pub fn list_profiles(config_path: &Option<String>) -> Result<(), anyhow::Error>{
+pub fn list_profiles(tags: &[String], config_path: &Option<String>) -> Result<(), anyhow::Error>{
     let path = check_config_path(config_path)?;
     let all_creds = parse_creds(&path)?;
+    let selected = select_profiles_by_tags(&all_creds, tags)?;
     let mut output: Vec<String> = Vec::new();
 
-    for profile in all_creds.profiles {
-        let name = profile.profile_name;
-        let status = match get_temp_cred_status(
-            &profile.temporary_credentials.expiration
+    for profile in all_creds.profiles.iter().filter(|p| selected.contains(&p.profile_name)) {
+        let name = if profile.tags.is_empty() {
+            profile.profile_name.to_owned()
+        } else {
+            format!("{} [{}]", profile.profile_name, profile.tags.join(", "))
+        };
+
+        let mut status = match get_temp_cred_status(
+            &profile.temporary_credentials.expiration,
+            get_refresh_threshold(&all_creds, profile)?,
         )? {
             TempCredStatus::Empty => "<- empty",
             TempCredStatus::Expired => "<- expired",
+            TempCredStatus::ExpiringSoon => "<- expiring soon",
             TempCredStatus::Ok => "",
-        };
+        }.to_string();
+
+        // A bad creation date shouldn't hide the other profiles, and `doctor` reports it anyway
+        match is_access_key_overdue(&profile.permanent_credentials.access_key_created) {
+            Ok(true) => status = format!("{} <- rotate key", status).trim_start().to_string(),
+            Ok(false) => {},
+            Err(e) => eprintln!("WARNING: profile `{}`: {}", profile.profile_name, e),
+        }
 
-        if name == all_creds.default {
-            output.push(format!("{} {} {}", name, "<- default".to_string(), &status))
+        if profile.profile_name == all_creds.default {
+            output.push(format!("{} {} {}", name, "<- default", &status))
         } else {
             output.push(format!("{} {}", name, &status));
         }
     }
 
     // Sort alphabetically
-    output.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+    output.sort_by_key(|a| a.to_lowercase());
 
-    Ok(println!("{}", output.join("\n")))
+    println!("{}", output.join("\n"));
+    Ok(())
 }
\ No newline at end of file
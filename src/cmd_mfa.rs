@@ -0,0 +1,249 @@
+use std::time::Duration;
+use anyhow::{anyhow, Error};
+use inquire::{self, validator::Validation};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use crate::{
+    cmd_rotate::create_iam_client,
+    totp,
+    types::MfaDevice,
+    utils::{
+        check_config_path, get_account_id, get_mfa_device_label, parse_creds, resolve_profile_name,
+        upsert_profile, write_creds,
+    },
+};
+
+/// The characters that are escaped in an otpauth label, i.e. all but RFC 3986's unreserved characters
+const LABEL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// Creates a virtual MFA device for the profile's IAM user, enables it, and saves its serial number
+/// to the profile
+#[::tokio::main]
+pub async fn enroll_mfa_device(
+    profile_name: &Option<String>,
+    device_name: &Option<String>,
+    store_seed: bool,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let mut profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p.to_owned(),
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    if profile.web_identity.is_some() {
+        return Err(anyhow!("profile `{}` assumes a role with a web identity and has no IAM user", name));
+    }
+
+    let iam_client = create_iam_client(&profile)?;
+    let user_name = iam_client
+        .get_user()
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to get IAM user: {}", e))?
+        .user()
+        .ok_or_else(|| anyhow!("no user returned in response"))?
+        .user_name()
+        .to_string();
+
+    let device = iam_client
+        .create_virtual_mfa_device()
+        .virtual_mfa_device_name(device_name.as_deref().unwrap_or(&user_name))
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to create virtual MFA device: {}", e))?
+        .virtual_mfa_device
+        .ok_or_else(|| anyhow!("no virtual MFA device returned in response"))?;
+
+    let serial_number = device.serial_number().to_string();
+    let seed = match device.base32_string_seed() {
+        Some(s) => String::from_utf8_lossy(s.as_ref()).to_string(),
+        None => return Err(anyhow!("no seed returned for virtual MFA device `{}`", serial_number)),
+    };
+
+    let uri = build_otpauth_uri(&user_name, get_account_id(&serial_number).unwrap_or_default(), &seed);
+
+    // Light modules are drawn as blocks, since most terminals use a dark background
+    let qr_code = QrCode::new(uri.as_bytes())?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+
+    println!("Scan this QR code with your authenticator app, or enter the key `{}` manually:\n\n{}\n", seed, qr_code);
+
+    let store_seed = store_seed || inquire::Confirm::new("Would you like aws-creds to store the key and generate MFA codes itself? (anyone who can read the config file will then be able to generate codes)")
+        .with_default(false)
+        .prompt()
+        .map_err(|_| anyhow!("failed to get user confirmation for storing the MFA key"))?;
+
+    let (code1, code2) = if store_seed {
+        // AWS wants two consecutive codes, so wait for the next time step before generating the second one
+        let code1 = totp::generate_current_code(&seed)?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        eprintln!("Waiting for the next MFA code...");
+        tokio::time::sleep(Duration::from_secs(totp::TIME_STEP - now % totp::TIME_STEP + 1)).await;
+        (code1, totp::generate_current_code(&seed)?)
+    } else {
+        (prompt_code("First MFA code:")?, prompt_code("Second MFA code (wait for the code to change):")?)
+    };
+
+    let enabled = iam_client
+        .enable_mfa_device()
+        .user_name(&user_name)
+        .serial_number(&serial_number)
+        .authentication_code1(code1)
+        .authentication_code2(code2)
+        .send()
+        .await;
+
+    if let Err(e) = enabled {
+        // Don't leave an unusable device behind
+        let _ = iam_client.delete_virtual_mfa_device().serial_number(&serial_number).send().await;
+        return Err(anyhow!("failed to enable MFA device: {}", e));
+    }
+
+    let device = MfaDevice {
+        label: get_mfa_device_label(&serial_number),
+        serial_number: serial_number.to_owned(),
+        totp_seed: if store_seed { Some(seed) } else { None },
+    };
+
+    let devices = &mut profile.permanent_credentials.mfa_devices;
+    devices.retain(|d| d.label != device.label);
+    devices.push(device);
+    upsert_profile(&mut all_creds, profile);
+    write_creds(&all_creds, &path)?;
+
+    println!("MFA device `{}` enabled for profile `{}`", serial_number, name);
+    Ok(())
+}
+
+/// Adds an existing MFA device to a profile, replacing any device with the same label
+pub fn add_mfa_device(
+    profile_name: &Option<String>,
+    serial_number: &str,
+    label: &Option<String>,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let mut profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p.to_owned(),
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    let device = MfaDevice {
+        label: label.to_owned().unwrap_or_else(|| get_mfa_device_label(serial_number)),
+        serial_number: serial_number.to_owned(),
+        totp_seed: None,
+    };
+
+    let devices = &mut profile.permanent_credentials.mfa_devices;
+    devices.retain(|d| d.label != device.label);
+    devices.push(device.to_owned());
+    upsert_profile(&mut all_creds, profile);
+    write_creds(&all_creds, &path)?;
+
+    println!("MFA device `{}` added to profile `{}`", device.label, name);
+    Ok(())
+}
+
+/// Removes an MFA device from a profile. The device itself is left untouched in AWS.
+pub fn remove_mfa_device(profile_name: &Option<String>, label: &str, config_path: &Option<String>) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let mut profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p.to_owned(),
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    let perm_creds = &mut profile.permanent_credentials;
+    if !perm_creds.mfa_devices.iter().any(|d| d.label == label) {
+        return Err(anyhow!("MFA device `{}` doesn't exist for profile `{}`", label, name));
+    }
+
+    perm_creds.mfa_devices.retain(|d| d.label != label);
+    if perm_creds.last_mfa_device.as_deref() == Some(label) {
+        perm_creds.last_mfa_device = None;
+    }
+
+    upsert_profile(&mut all_creds, profile);
+    write_creds(&all_creds, &path)?;
+
+    println!("MFA device `{}` removed from profile `{}`", label, name);
+    Ok(())
+}
+
+/// Prints the MFA devices of a profile, marking the one that was used last
+pub fn list_mfa_devices(profile_name: &Option<String>, config_path: &Option<String>) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let perm_creds = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => &p.permanent_credentials,
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    let mut output: Vec<String> = Vec::new();
+    for d in perm_creds.mfa_devices.iter() {
+        let mut line = format!("{} ({})", d.label, d.serial_number);
+        if perm_creds.last_mfa_device.as_ref() == Some(&d.label) {
+            line.push_str(" <- last used");
+        }
+        if d.totp_seed.is_some() {
+            line.push_str(" <- codes generated by aws-creds");
+        }
+        output.push(line);
+    }
+
+    println!("{}", output.join("\n"));
+    Ok(())
+}
+
+fn prompt_code(message: &str) -> Result<String, Error> {
+    inquire::Text::new(message)
+        .with_validator(|input: &str| {
+            if input.trim().len() == 6 && input.trim().chars().all(|c| c.is_ascii_digit()) {
+                Ok(Validation::Valid)
+            } else {
+                Ok(Validation::Invalid("MFA codes are 6 digits".into()))
+            }
+        })
+        .prompt()
+        .map(|c| c.trim().to_string())
+        .map_err(|_| anyhow!("failed to retrieve MFA Code"))
+}
+
+
+/// Builds the URI that authenticator apps read from the QR code. IAM user names may contain `+`, `=`,
+/// `,` and `@`, so the label is escaped.
+fn build_otpauth_uri(user_name: &str, account_id: &str, seed: &str) -> String {
+    format!(
+        "otpauth://totp/Amazon%20Web%20Services:{}@{}?secret={}&issuer=Amazon%20Web%20Services",
+        utf8_percent_encode(user_name, LABEL_ENCODE_SET),
+        utf8_percent_encode(account_id, LABEL_ENCODE_SET),
+        seed,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_otpauth_uri() {
+        assert_eq!(
+            build_otpauth_uri("alice", "123456789012", "SEED"),
+            "otpauth://totp/Amazon%20Web%20Services:alice@123456789012?secret=SEED&issuer=Amazon%20Web%20Services",
+        );
+        assert_eq!(
+            build_otpauth_uri("bob+ops=x,y@example.com", "123456789012", "SEED"),
+            "otpauth://totp/Amazon%20Web%20Services:bob%2Bops%3Dx%2Cy%40example.com@123456789012?secret=SEED&issuer=Amazon%20Web%20Services",
+        );
+    }
+}
\ No newline at end of file
@@ -1,12 +1,17 @@
-use std::{fs, path::Path, process};
+use std::{collections::BTreeMap, fs, path::Path, process};
+use aws_sdk_sts::config::Credentials;
 use anyhow::{anyhow, Error};
 use inquire::{self, validator::Validation};
 use crate::{
-    types::{AwsProfiles, CredentialsProfile, FileError, PermanentCredentials, TemporaryCredentials},
-    utils::{get_default_config_path, parse_creds, write_creds},
+    audit,
+    cmd_get::create_sts_client,
+    cmd_rotate::build_iam_client,
+    types::{AwsProfiles, CredentialsProfile, FileError, MfaDevice, PermanentCredentials, TemporaryCredentials, WebIdentity},
+    utils::{get_mfa_device_label, parse_creds, resolve_config_path, upsert_profile, write_creds},
 };
 
-pub fn create_profile(profile_name: &String, config_path: &Option<String>) -> Result<(), Error> {
+#[::tokio::main]
+pub async fn create_profile(profile_name: &String, web_identity: bool, config_path: &Option<String>) -> Result<(), Error> {
     // Ensures that the inquire::<String> methods below won't accept a blank string
     let string_validator = |input: &str| {
         if input.trim().is_empty() {
@@ -21,29 +26,73 @@ pub fn create_profile(profile_name: &String, config_path: &Option<String>) -> Re
         .prompt()
         .map_err(|_| anyhow!("failed to get user confirmation for default"))?;
 
-    let access_key_id = inquire::Password::new("AWS_ACCESS_KEY_ID:")
-        .without_confirmation()
-        .with_validator(string_validator)
-        .prompt()
-        .map_err(|_| anyhow!("failed to get user input for access_key_id"))?;
+    let web_identity = if web_identity {
+        Some(prompt_web_identity(profile_name)?)
+    } else {
+        None
+    };
 
-    let secret_access_key = inquire::Password::new("AWS_SECRET_ACCESS_KEY:")
-        .without_confirmation()
-        .with_validator(string_validator)
-        .prompt()
-        .map_err(|_| anyhow!("failed to get user input for secret_access_key"))?;
+    let (access_key_id, secret_access_key) = match web_identity {
+        Some(_) => ("".to_string(), "".to_string()),
+        None => (
+            inquire::Password::new("AWS_ACCESS_KEY_ID:")
+                .without_confirmation()
+                .with_validator(string_validator)
+                .prompt()
+                .map_err(|_| anyhow!("failed to get user input for access_key_id"))?,
+            inquire::Password::new("AWS_SECRET_ACCESS_KEY:")
+                .without_confirmation()
+                .with_validator(string_validator)
+                .prompt()
+                .map_err(|_| anyhow!("failed to get user input for secret_access_key"))?,
+        ),
+    };
 
-    let mfa_serial_number = inquire::Text::new("AWS MFA Device Serial Number:")
-        .with_validator(string_validator)
-        .prompt()
-        .map_err(|_| anyhow!("failed to get user input for mfa_serial_number"))?;
+    // Default to the region of the environment, like the AWS CLI and SDKs do
+    let default_region = std::env::var("AWS_REGION")
+        .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or("us-east-1".to_string());
 
     let region = inquire::Text::new("AWS Region:")
-        .with_default("us-east-1")
+        .with_default(&default_region)
         .with_validator(string_validator)
         .prompt()
         .map_err(|_| anyhow!("failed to get user input for region"))?;
 
+    let mut permanent_credentials = PermanentCredentials {
+        access_key_id,
+        secret_access_key,
+        region,
+        mfa_devices: Vec::new(),
+        last_mfa_device: None,
+        access_key_created: None,
+    };
+
+    // Web identity profiles have no access key to verify, and authenticate with their token instead of MFA
+    let mut caller_arn = None;
+    if web_identity.is_none() {
+        let (arn, mfa_devices, access_key_created) = match discover_account_details(&permanent_credentials).await {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("WARNING: {}", e);
+                let save = inquire::Confirm::new("The access keys couldn't be verified. Would you like to save the profile anyway?")
+                    .with_default(false)
+                    .prompt()
+                    .map_err(|_| anyhow!("failed to get user confirmation for saving"))?;
+
+                if !save {
+                    process::exit(1);
+                }
+
+                (None, Vec::new(), None)
+            },
+        };
+
+        permanent_credentials.mfa_devices = select_mfa_devices(mfa_devices)?;
+        permanent_credentials.access_key_created = access_key_created;
+        caller_arn = arn;
+    }
+
     let path = create_config_file_if_not_exists(config_path)?;
     let mut creds = match parse_creds(&path) {
         Ok(c) => {
@@ -67,12 +116,15 @@ pub fn create_profile(profile_name: &String, config_path: &Option<String>) -> Re
         Err(e) => {
             match e {
                 FileError::NotFound => {
-                    let creds = AwsProfiles{
+                    AwsProfiles{
                         default: "".to_string(),
                         profiles: Vec::new(),
-                    };
-
-                    creds
+                        refresh_threshold: None,
+                        interactive_picker: false,
+                        git_hosts: BTreeMap::new(),
+                        docker_registries: BTreeMap::new(),
+                        disable_audit_log: false,
+                    }
                 },
                 FileError::Other { message } => return Err(anyhow!("{}", message)),
             }
@@ -84,44 +136,158 @@ pub fn create_profile(profile_name: &String, config_path: &Option<String>) -> Re
         creds.default = profile_name.to_owned();
     }
 
-    creds.profiles.push(CredentialsProfile {
+    let needs_mfa_device = web_identity.is_none() && permanent_credentials.mfa_devices.is_empty();
+    upsert_profile(&mut creds, CredentialsProfile {
         profile_name: profile_name.to_owned(),
-        permanent_credentials: PermanentCredentials {
-            access_key_id,
-            secret_access_key,
-            mfa_serial_number,
-            region,
-        },
+        permanent_credentials,
         temporary_credentials: TemporaryCredentials {
             access_key_id: "".to_string(),
             secret_access_key: "".to_string(),
             session_token: "".to_string(),
             expiration: "".to_string(),
-        }
+        },
+        tags: Vec::new(),
+        refresh_threshold: None,
+        last_used: None,
+        web_identity,
+        caller_arn,
      });
 
      write_creds(&creds, &path)?;
+     audit::record_event(&creds, &path, audit::new_event("new", profile_name, "", &[]))?;
 
-    Ok(println!("Profile `{}` created at `{}`", profile_name, path))
+    println!("Profile `{}` created at `{}`", profile_name, path);
+    if needs_mfa_device {
+        println!("Profile `{}` has no MFA device; run `aws-creds mfa enroll --profile {}` to set one up", profile_name, profile_name);
+    }
+    Ok(())
 }
 
-fn create_config_file_if_not_exists(config_path: &Option<String>) -> Result<String, Error> {
-    let path_str = match config_path{
-        Some(p) => p,
-        None => &get_default_config_path()?,
+/// Asks for the role to assume and where to read the OIDC token from
+fn prompt_web_identity(profile_name: &str) -> Result<WebIdentity, Error> {
+    let role_arn = inquire::Text::new("Role ARN:")
+        .with_validator(|input: &str| {
+            if input.trim().starts_with("arn:aws") && input.contains(":role/") {
+                Ok(Validation::Valid)
+            } else {
+                Ok(Validation::Invalid("expected a role ARN like `arn:aws:iam::123456789012:role/ci`".into()))
+            }
+        })
+        .prompt()
+        .map_err(|_| anyhow!("failed to get user input for role_arn"))?;
+
+    let role_session_name = inquire::Text::new("Role Session Name:")
+        .with_default(&format!("aws-creds-{}", profile_name))
+        .prompt()
+        .map_err(|_| anyhow!("failed to get user input for role_session_name"))?;
+
+    let from_file = inquire::Select::new("Read the web identity token from:", vec!["A file", "The output of a command"])
+        .raw_prompt()
+        .map_err(|_| anyhow!("failed to get user selection for the token source"))?
+        .index == 0;
+
+    let source = inquire::Text::new(if from_file { "Token File:" } else { "Token Command:" })
+        .with_validator(|input: &str| {
+            if input.trim().is_empty() {
+                Ok(Validation::Invalid("field cannot be empty".into()))
+            } else {
+                Ok(Validation::Valid)
+            }
+        })
+        .prompt()
+        .map_err(|_| anyhow!("failed to get user input for the token source"))?
+        .trim()
+        .to_string();
+
+    Ok(WebIdentity {
+        role_arn: role_arn.trim().to_string(),
+        role_session_name: role_session_name.trim().to_string(),
+        token_file: if from_file { Some(source.to_owned()) } else { None },
+        token_command: if from_file { None } else { Some(source) },
+    })
+}
+
+/// Verifies the access keys, returning the ARN of the IAM user, the serial numbers of its MFA devices and
+/// the creation date of the access key. Failing to list either is not an error, since the user may not
+/// be allowed to.
+async fn discover_account_details(perm_creds: &PermanentCredentials) -> Result<(Option<String>, Vec<String>, Option<String>), Error> {
+    let sts_client = create_sts_client(perm_creds)?;
+    let identity = sts_client
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to verify access keys: {}", e))?;
+
+    println!("Access keys belong to `{}`", identity.arn().unwrap_or_default());
+
+    let iam_client = build_iam_client(
+        Credentials::new(&perm_creds.access_key_id, &perm_creds.secret_access_key, None, None, ""),
+        &perm_creds.region,
+    );
+    let mfa_devices = match iam_client.list_mfa_devices().send().await {
+        Ok(r) => r.mfa_devices().iter().map(|d| d.serial_number().to_string()).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    let access_key_created = match iam_client.list_access_keys().send().await {
+        Ok(r) => r.access_key_metadata()
+            .iter()
+            .find(|k| k.access_key_id() == Some(perm_creds.access_key_id.as_str()))
+            .and_then(|k| k.create_date())
+            .map(|d| d.to_string()),
+        Err(_) => None,
+    };
+
+    Ok((identity.arn().map(|a| a.to_string()), mfa_devices, access_key_created))
+}
+
+/// Lets the user choose from the discovered MFA devices, or enter a serial number by hand
+fn select_mfa_devices(mfa_devices: Vec<String>) -> Result<Vec<MfaDevice>, Error> {
+    let to_device = |serial_number: String| MfaDevice {
+        label: get_mfa_device_label(&serial_number),
+        serial_number,
+        totp_seed: None,
     };
 
+    if !mfa_devices.is_empty() {
+        let selection = inquire::MultiSelect::new("AWS MFA Devices:", mfa_devices)
+            .with_all_selected_by_default()
+            .with_help_message("Deselect every device to enter a serial number manually")
+            .prompt()
+            .map_err(|_| anyhow!("failed to get user selection for mfa_devices"))?;
+
+        if !selection.is_empty() {
+            return Ok(selection.into_iter().map(to_device).collect());
+        }
+    }
+
+    let mfa_serial_number = inquire::Text::new("AWS MFA Device Serial Number:")
+        .with_help_message("Leave empty for a profile without MFA, or to enroll a virtual MFA device later with `aws-creds mfa enroll`")
+        .prompt()
+        .map_err(|_| anyhow!("failed to get user input for mfa_serial_number"))?;
+
+    if mfa_serial_number.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![to_device(mfa_serial_number.trim().to_string())])
+}
+
+fn create_config_file_if_not_exists(config_path: &Option<String>) -> Result<String, Error> {
+    // Resolved like every other command's, so that a project's config file is created there
+    let path_str = resolve_config_path(config_path)?.0;
+
     let raw_path = Path::new(&path_str);
 
     // Check to make sure the file ends with creds.json
-    let file = raw_path.file_name().expect("failed to parse file name");
-    let file_str = file.to_str().expect("failed to parse file name as string");
+    let file = raw_path.file_name().ok_or_else(|| anyhow!("the path `{}` is missing a file name", path_str))?;
+    let file_str = file.to_str().ok_or_else(|| anyhow!("failed to parse file name as string"))?;
     if !file_str.contains(".json") {
         return Err(anyhow!("the file `{}` is invalid (missing the `.json` extension)", file_str))
     }
 
     // Create the directory
-    let dirs = raw_path.parent().expect("failed to parse parent path as directory");
+    let dirs = raw_path.parent().ok_or_else(|| anyhow!("failed to parse parent path of `{}` as directory", path_str))?;
 
     fs::create_dir_all(dirs)?;
     Ok(path_str.to_string())
@@ -0,0 +1,98 @@
+use std::time::SystemTime;
+use anyhow::{anyhow, Error};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use crate::{
+    cmd_show::load_temp_creds,
+    sigv4::presign_url,
+    utils::{check_config_path, format_duration, get_profile_region, get_remaining_lifetime, parse_creds, parse_duration},
+};
+
+/// The longest lifetime SigV4 allows for presigned URLs
+const MAX_EXPIRATION_DAYS: i64 = 7;
+
+/// Characters that S3 object keys keep unencoded in URLs
+const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~').remove(b'/');
+
+/// Returns a presigned URL for an S3 object. Since the URL stops working when the session it was
+/// signed with expires, its lifetime is capped at the session's remaining lifetime.
+pub fn presign_s3_url(
+    s3_uri: &str,
+    expires: &str,
+    method: &str,
+    region: &Option<String>,
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+) -> Result<String, Error> {
+    let (bucket, key) = parse_s3_uri(s3_uri)?;
+    let mut expires_in = parse_duration(expires)?;
+    if expires_in <= chrono::Duration::zero() || expires_in > chrono::Duration::days(MAX_EXPIRATION_DAYS) {
+        return Err(anyhow!("the expiration `{}` is invalid (presigned URLs last between 1s and {}d)", expires, MAX_EXPIRATION_DAYS));
+    }
+
+    let (name, temp_creds) = load_temp_creds(profile_name, config_path, &None, false, false)?;
+    if let Some(remaining) = get_remaining_lifetime(&temp_creds.expiration)? {
+        if remaining < expires_in {
+            eprintln!(
+                "WARNING: the URL will only work for {} instead of {}, since the temporary credentials for profile `{}` expire then",
+                format_duration(remaining),
+                format_duration(expires_in),
+                name,
+            );
+            expires_in = remaining;
+        }
+    }
+
+    let region = match region {
+        Some(r) => r.to_owned(),
+        None => get_profile_region(&parse_creds(&check_config_path(config_path)?)?, &name)?,
+    };
+
+    presign_url(
+        &temp_creds,
+        &region,
+        "s3",
+        &method.to_uppercase(),
+        &get_object_url(&bucket, &key, &region),
+        &[],
+        expires_in.to_std()?,
+        SystemTime::now(),
+    )
+}
+
+/// Splits `s3://bucket/key` into the bucket and key
+fn parse_s3_uri(s3_uri: &str) -> Result<(String, String), Error> {
+    match s3_uri.strip_prefix("s3://").and_then(|p| p.split_once('/')) {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok((bucket.to_string(), key.to_string())),
+        _ => Err(anyhow!("the S3 URI `{}` is invalid (expected `s3://bucket/key`)", s3_uri)),
+    }
+}
+
+/// Returns the URL of an object. Bucket names with dots don't match the wildcard certificate of
+/// virtual-hosted URLs, so those use path-style URLs.
+fn get_object_url(bucket: &str, key: &str, region: &str) -> String {
+    let key = utf8_percent_encode(key, KEY_ENCODE_SET);
+    if bucket.contains('.') {
+        format!("https://s3.{}.amazonaws.com/{}/{}", region, bucket, key)
+    } else {
+        format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_uri() {
+        assert_eq!(parse_s3_uri("s3://bucket/path/to/file.txt").unwrap(), ("bucket".to_string(), "path/to/file.txt".to_string()));
+        assert!(parse_s3_uri("s3://bucket").is_err());
+        assert!(parse_s3_uri("s3://bucket/").is_err());
+        assert!(parse_s3_uri("https://bucket/key").is_err());
+    }
+
+    #[test]
+    fn test_get_object_url() {
+        assert_eq!(get_object_url("bucket", "a b/c+d.txt", "eu-west-1"), "https://bucket.s3.eu-west-1.amazonaws.com/a%20b/c%2Bd.txt");
+        assert_eq!(get_object_url("my.bucket", "key", "us-east-1"), "https://s3.us-east-1.amazonaws.com/my.bucket/key");
+    }
+}
\ No newline at end of file
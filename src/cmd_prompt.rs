@@ -0,0 +1,68 @@
+use crate::{
+    shell::PROFILE_VAR,
+    types::AwsProfiles,
+    utils::{check_config_path, format_duration, parse_creds},
+};
+
+/// Returns a prompt segment for the profile marked in the environment, e.g. `prod (2h13m)`. It only
+/// reads the config file and never fails, since it runs every time the prompt is drawn.
+pub fn show_prompt(config_path: &Option<String>) -> String {
+    let name = match std::env::var(PROFILE_VAR) {
+        Ok(n) if !n.is_empty() => n,
+        _ => return "".to_string(),
+    };
+
+    let all_creds = match check_config_path(config_path).ok().and_then(|p| parse_creds(&p).ok()) {
+        Some(c) => c,
+        None => return name,
+    };
+
+    let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default();
+    format_prompt(&all_creds, &name, &access_key_id, chrono::Utc::now())
+}
+
+/// Formats the segment for a profile. Credentials that don't match the profile's stored session
+/// were replaced by a later `get`, so their lifetime is unknown.
+fn format_prompt(all_creds: &AwsProfiles, name: &str, access_key_id: &str, now: chrono::DateTime<chrono::Utc>) -> String {
+    let profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p,
+        None => return name.to_string(),
+    };
+
+    let temp_creds = &profile.temporary_credentials;
+    if access_key_id == profile.permanent_credentials.access_key_id {
+        return format!("{} (permanent)", name);
+    }
+
+    if access_key_id != temp_creds.access_key_id {
+        return format!("{} (stale)", name);
+    }
+
+    match chrono::DateTime::parse_from_rfc3339(&temp_creds.expiration) {
+        Ok(e) if e.with_timezone(&chrono::Utc) > now => {
+            format!("{} ({})", name, format_duration(e.with_timezone(&chrono::Utc) - now))
+        },
+        Ok(_) => format!("{} (expired)", name),
+        Err(_) => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_prompt() {
+        let dir = std::env::current_dir().expect("failed to retrieve working directory").join("test_assets").join("creds.json");
+        let mut all_creds = parse_creds(&dir.to_str().unwrap().to_string()).unwrap();
+        all_creds.profiles[1].temporary_credentials.expiration = "2025-01-01T14:13:30Z".to_string();
+        let now = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let name = all_creds.profiles[1].profile_name.to_owned();
+        let temp_key = all_creds.profiles[1].temporary_credentials.access_key_id.to_owned();
+
+        assert_eq!(format_prompt(&all_creds, &name, &temp_key, now), format!("{} (2h13m)", name));
+        assert_eq!(format_prompt(&all_creds, &name, "ASIAOLDKEY", now), format!("{} (stale)", name));
+        assert_eq!(format_prompt(&all_creds, &name, &temp_key, now + chrono::Duration::hours(3)), format!("{} (expired)", name));
+        assert_eq!(format_prompt(&all_creds, "unknown", &temp_key, now), "unknown");
+    }
+}
\ No newline at end of file
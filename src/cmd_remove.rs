@@ -1,17 +1,41 @@
-use crate::{types::CredentialsProfile, utils::{check_config_path, parse_creds, write_creds}};
+use anyhow::anyhow;
+use crate::{audit, types::CredentialsProfile, utils::{check_config_path, parse_creds, select_profiles_by_tags, write_creds}};
 
-pub fn remove_profile(profile_name: &String, config_path: &Option<String>) -> Result<(), anyhow::Error> {
+pub fn remove_profile(profile_name: &Option<String>, tags: &[String], config_path: &Option<String>) -> Result<(), anyhow::Error> {
     let path = check_config_path(config_path)?;
     let mut all_creds= parse_creds(&path)?;
+
+    let names = match profile_name {
+        Some(n) => vec![n.to_owned()],
+        None => {
+            let names = select_profiles_by_tags(&all_creds, tags)?;
+            let confirmation = inquire::Confirm::new(&format!("Would you like to remove the profiles {}?", names.join(", ")))
+                .with_default(false)
+                .prompt()
+                .map_err(|_| anyhow!("failed to get user confirmation for removal"))?;
+
+            if !confirmation {
+                return Ok(());
+            }
+
+            names
+        },
+    };
+
+    // Recorded before the removal, while the profiles' identities are still known
+    for p in all_creds.profiles.iter().filter(|p| names.contains(&p.profile_name)) {
+        audit::record_event(&all_creds, &path, audit::new_event("remove", &p.profile_name, "", &[]))?;
+    }
+
     let mut profiles_list: Vec<CredentialsProfile> = Vec::new();
 
     for p in all_creds.profiles.iter_mut() {
-        if &p.profile_name != profile_name {
+        if !names.contains(&p.profile_name) {
             profiles_list.push(p.to_owned());
         }
     }
 
     all_creds.profiles = profiles_list;
 
-    Ok(write_creds(&all_creds, &path)?)
+    write_creds(&all_creds, &path)
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::utils::{check_config_path, parse_creds, write_creds};
+use crate::{audit, utils::{check_config_path, parse_creds, write_creds}};
 
 pub fn rename_profile(
     old_profile: &str,
@@ -7,6 +7,9 @@ pub fn rename_profile(
 ) -> Result<(), anyhow::Error> {
     let path = check_config_path(config_path)?;
     let mut all_creds = parse_creds(&path)?;
+    if all_creds.profiles.iter().any(|p| p.profile_name == new_profile) {
+        return Err(anyhow::anyhow!("profile `{}` already exists", new_profile));
+    }
 
     for p in all_creds.profiles.iter_mut() {
         if p.profile_name == old_profile {
@@ -17,8 +20,13 @@ pub fn rename_profile(
             }
 
             write_creds(&all_creds, &path)?;
+            // Recorded under the old name with the new one alongside, so the history of either finds it
+            let mut audit_event = audit::new_event("rename", old_profile, "", &[]);
+            audit_event.renamed_to = Some(new_profile.to_string());
+            audit::record_event(&all_creds, &path, audit_event)?;
 
-            return Ok(println!("Profile `{}` renamed to `{}`", old_profile, new_profile));
+            println!("Profile `{}` renamed to `{}`", old_profile, new_profile);
+            return Ok(());
         }
     }
 
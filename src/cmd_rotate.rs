@@ -0,0 +1,128 @@
+use std::time::Duration;
+use aws_config::Region;
+use aws_sdk_iam::{config::Credentials, types::StatusType, Config};
+use anyhow::{anyhow, Error};
+use crate::{
+    cmd_get::create_sts_client,
+    types::CredentialsProfile,
+    utils::{
+        check_config_path, get_temp_cred_status, parse_creds, resolve_profile_name, upsert_profile,
+        write_creds, TempCredStatus,
+    },
+};
+
+/// How many times the new access key is checked before giving up, since new keys take a few seconds
+/// to become usable
+const VERIFY_ATTEMPTS: u32 = 10;
+
+/// Replaces the access key of a profile with a new one, then deactivates and deletes the old key
+#[::tokio::main]
+pub async fn rotate_access_key(profile_name: &Option<String>, config_path: &Option<String>) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+    let mut profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p.to_owned(),
+        None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+    };
+
+    if profile.web_identity.is_some() {
+        return Err(anyhow!("profile `{}` assumes a role with a web identity and has no IAM user", name));
+    }
+
+    let iam_client = create_iam_client(&profile)?;
+    let old_access_key_id = profile.permanent_credentials.access_key_id.to_owned();
+
+    let new_key = iam_client
+        .create_access_key()
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to create access key: {}", e))?
+        .access_key
+        .ok_or_else(|| anyhow!("no access key returned in response"))?;
+
+    let mut new_perm_creds = profile.permanent_credentials.clone();
+    new_perm_creds.access_key_id = new_key.access_key_id().to_string();
+    new_perm_creds.secret_access_key = new_key.secret_access_key().to_string();
+    new_perm_creds.access_key_created = Some(match new_key.create_date() {
+        Some(d) => d.to_string(),
+        None => chrono::Utc::now().to_rfc3339(),
+    });
+
+    // Make sure the new key works before giving up the old one
+    let sts_client = create_sts_client(&new_perm_creds)?;
+    let mut verified = false;
+    for _ in 0..VERIFY_ATTEMPTS {
+        if sts_client.get_caller_identity().send().await.is_ok() {
+            verified = true;
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    if !verified {
+        let _ = iam_client.delete_access_key().access_key_id(&new_perm_creds.access_key_id).send().await;
+        return Err(anyhow!("failed to verify the new access key; it has been deleted and the old key is unchanged"));
+    }
+
+    // Save the new key before touching the old one, so that it can't be lost if a later step fails
+    profile.permanent_credentials = new_perm_creds;
+    upsert_profile(&mut all_creds, profile.to_owned());
+    write_creds(&all_creds, &path)?;
+
+    // Without a session, the client was signing with the old key, which stops working once it's
+    // deactivated, so the old key is deactivated and deleted with a client for the new one
+    let iam_client = create_iam_client(&profile)?;
+    iam_client
+        .update_access_key()
+        .access_key_id(&old_access_key_id)
+        .status(StatusType::Inactive)
+        .send()
+        .await
+        .map_err(|e| anyhow!("the new access key was saved, but deactivating the old key `{}` failed: {}", old_access_key_id, e))?;
+
+    iam_client
+        .delete_access_key()
+        .access_key_id(&old_access_key_id)
+        .send()
+        .await
+        .map_err(|e| anyhow!("the new access key was saved, but deleting the old key `{}` failed: {}", old_access_key_id, e))?;
+
+    println!("Access key for profile `{}` rotated", name);
+    Ok(())
+}
+
+/// Creates an IAM client for a profile. The profile's temporary credentials are preferred, since
+/// they're authenticated with MFA, which IAM policies commonly require for managing keys.
+pub fn create_iam_client(profile: &CredentialsProfile) -> Result<aws_sdk_iam::Client, Error> {
+    let temp_creds = &profile.temporary_credentials;
+    let creds = match get_temp_cred_status(&temp_creds.expiration, chrono::Duration::zero())? {
+        TempCredStatus::Ok | TempCredStatus::ExpiringSoon => Credentials::new(
+            &temp_creds.access_key_id,
+            &temp_creds.secret_access_key,
+            Some(temp_creds.session_token.to_owned()),
+            None,
+            "",
+        ),
+        TempCredStatus::Empty | TempCredStatus::Expired => Credentials::new(
+            &profile.permanent_credentials.access_key_id,
+            &profile.permanent_credentials.secret_access_key,
+            None,
+            None,
+            "",
+        ),
+    };
+
+    Ok(build_iam_client(creds, &profile.permanent_credentials.region))
+}
+
+pub fn build_iam_client(creds: Credentials, region: &str) -> aws_sdk_iam::Client {
+    let conf = Config::builder()
+        .region(Region::new(region.to_owned()))
+        .credentials_provider(creds)
+        .behavior_version_latest()
+        .build();
+
+    aws_sdk_iam::Client::from_conf(conf)
+}
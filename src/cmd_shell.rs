@@ -0,0 +1,201 @@
+use std::{fs, io::Write, path::{Path, PathBuf}, process::{self, Command}, thread};
+use anyhow::{anyhow, Error};
+use clap::ValueEnum;
+use crate::{
+    cmd_show::{get_env_vars, get_extra_env_vars, load_temp_creds},
+    shell::{quote, ExtraVar, Shell, PROFILE_VAR},
+    utils::{
+        check_config_path, format_duration, get_refresh_threshold, parse_creds, parse_duration,
+        random_suffix, resolve_profile_name,
+    },
+};
+
+/// Starts `$SHELL` with a profile's temporary credentials and the profile's name in its prompt. The
+/// credentials only exist in the subshell's environment, so they're gone once it exits. A warning is
+/// printed in the subshell when they're about to expire.
+pub fn spawn_shell(
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+    min_lifetime: &Option<String>,
+    strict: bool,
+) -> Result<(), Error> {
+    let path = check_config_path(config_path)?;
+    let all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+
+    if let Ok(current) = std::env::var(PROFILE_VAR) {
+        if !current.is_empty() && current != name {
+            let nest = inquire::Confirm::new(&format!("This shell already has the credentials of profile `{}`. Would you like to open a shell for profile `{}` inside it?", current, name))
+                .with_default(false)
+                .prompt()
+                .map_err(|_| anyhow!("failed to get user confirmation for nesting"))?;
+
+            if !nest {
+                process::exit(0);
+            }
+        }
+    }
+
+    let (name, temp_creds) = load_temp_creds(&Some(name), config_path, min_lifetime, strict, false)?;
+    let threshold = match min_lifetime {
+        Some(l) => parse_duration(l)?,
+        None => match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+            Some(p) => get_refresh_threshold(&all_creds, p)?,
+            None => return Err(anyhow!("profile `{}` doesn't exist", name)),
+        },
+    };
+
+    let program = std::env::var("SHELL").unwrap_or("/bin/sh".to_string());
+    let rc_dir = create_rc_dir()?;
+    let mut command = Command::new(&program);
+    command
+        .envs(get_env_vars(&temp_creds))
+        .envs(get_extra_env_vars(&name, &temp_creds, ExtraVar::value_variants(), config_path)?);
+
+    if let Err(e) = set_prompt(&mut command, &program, &name, &rc_dir) {
+        let _ = fs::remove_dir_all(&rc_dir);
+        return Err(e);
+    }
+
+    if let Ok(expiration) = chrono::DateTime::parse_from_rfc3339(&temp_creds.expiration) {
+        let expiration = expiration.with_timezone(&chrono::Utc);
+        let name = name.to_owned();
+
+        // The thread ends with the process, which exits as soon as the subshell does
+        thread::spawn(move || {
+            sleep_until(expiration.checked_sub_signed(threshold).unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC));
+            let remaining = expiration - chrono::Utc::now();
+            eprintln!(
+                "\nWARNING: the temporary credentials for profile `{}` expire in {}; run `aws-creds get` and start a new shell to refresh them",
+                name,
+                format_duration(remaining),
+            );
+
+            sleep_until(expiration);
+            eprintln!("\nWARNING: the temporary credentials for profile `{}` have expired", name);
+        });
+    }
+
+    let status = command.status().map_err(|e| anyhow!("failed to run `{}`: {}", program, e));
+    let _ = fs::remove_dir_all(&rc_dir);
+
+    process::exit(status?.code().unwrap_or(1));
+}
+
+/// Prefixes the subshell's prompt with the profile's name. bash and zsh read their prompt from their
+/// rc files, so they get an rc file that sources the user's own before changing the prompt.
+fn set_prompt(command: &mut Command, program: &str, name: &str, rc_dir: &Path) -> Result<(), Error> {
+    let prefix = format!("[aws:{}] ", name);
+    let shell_name = Path::new(program).file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    match shell_name.as_str() {
+        "bash" => {
+            let rc_file = write_rc_file(rc_dir, "bashrc", &format!(
+                "[ -f ~/.bashrc ] && . ~/.bashrc\nPS1={}\"$PS1\"\n",
+                quote(Shell::Bash, &prefix),
+            ))?;
+            command.arg("--rcfile").arg(rc_file);
+        },
+        "zsh" => {
+            // zsh reads its rc files from ZDOTDIR, so point it at rc files that restore the original
+            let zdotdir = std::env::var("ZDOTDIR").unwrap_or_default();
+            write_rc_file(rc_dir, ".zshenv", "[ -f \"${AWS_CREDS_ZDOTDIR:-$HOME}/.zshenv\" ] && . \"${AWS_CREDS_ZDOTDIR:-$HOME}/.zshenv\"\n")?;
+            write_rc_file(rc_dir, ".zshrc", &format!(
+                "ZDOTDIR=\"${{AWS_CREDS_ZDOTDIR:-$HOME}}\"\nunset AWS_CREDS_ZDOTDIR\n[ -f \"$ZDOTDIR/.zshrc\" ] && . \"$ZDOTDIR/.zshrc\"\nPROMPT={}\"$PROMPT\"\n",
+                quote(Shell::Zsh, &prefix),
+            ))?;
+            command.env("AWS_CREDS_ZDOTDIR", zdotdir).env("ZDOTDIR", rc_dir);
+        },
+        "fish" => {
+            command.arg("--init-command").arg(format!(
+                "functions -q fish_prompt; and functions -c fish_prompt __aws_creds_fish_prompt; function fish_prompt; printf '%%s' {}; functions -q __aws_creds_fish_prompt; and __aws_creds_fish_prompt; end",
+                quote(Shell::Fish, &prefix),
+            ));
+        },
+        _ => {
+            command.env("PS1", format!("{}$ ", prefix));
+        },
+    }
+
+    Ok(())
+}
+
+/// Creates a new directory for the rc files that only the user can access. The name is random and
+/// creating it fails if it already exists, so another user can't slip their own rc files in.
+fn create_rc_dir() -> Result<PathBuf, Error> {
+    let rc_dir = std::env::temp_dir().join(format!("aws-creds-shell-{}", random_suffix()));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(&rc_dir).map_err(|e| anyhow!("failed to create `{}`: {}", rc_dir.display(), e))?;
+    Ok(rc_dir)
+}
+
+/// Writes a new rc file, failing rather than following a file or symlink that's already there
+fn write_rc_file(rc_dir: &Path, file_name: &str, contents: &str) -> Result<PathBuf, Error> {
+    let path = rc_dir.join(file_name);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(&path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|e| anyhow!("failed to write `{}`: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
+fn sleep_until(time: chrono::DateTime<chrono::Utc>) {
+    if let Ok(duration) = (time - chrono::Utc::now()).to_std() {
+        thread::sleep(duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_set_prompt() {
+        let rc_dir = create_rc_dir().unwrap();
+
+        let mut command = Command::new("/bin/sh");
+        set_prompt(&mut command, "/bin/sh", "prod", &rc_dir).unwrap();
+        let ps1 = command.get_envs().find(|(k, _)| *k == "PS1").and_then(|(_, v)| v);
+        assert_eq!(ps1, Some(std::ffi::OsStr::new("[aws:prod] $ ")));
+
+        let mut command = Command::new("/bin/bash");
+        set_prompt(&mut command, "/bin/bash", "prod", &rc_dir).unwrap();
+        let rc_file = rc_dir.join("bashrc");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), vec![std::ffi::OsStr::new("--rcfile"), rc_file.as_os_str()]);
+        assert!(fs::read_to_string(&rc_file).unwrap().ends_with("PS1='[aws:prod] '\"$PS1\"\n"));
+
+        // The rc files are never overwritten
+        assert!(write_rc_file(&rc_dir, "bashrc", "").is_err());
+
+        fs::remove_dir_all(&rc_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_rc_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let rc_dir = create_rc_dir().unwrap();
+        let other_dir = create_rc_dir().unwrap();
+        assert_ne!(rc_dir, other_dir);
+        assert_eq!(fs::metadata(&rc_dir).unwrap().permissions().mode() & 0o777, 0o700);
+
+        fs::remove_dir_all(&rc_dir).unwrap();
+        fs::remove_dir_all(&other_dir).unwrap();
+    }
+}
\ No newline at end of file
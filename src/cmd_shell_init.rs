@@ -0,0 +1,59 @@
+use crate::shell::{format_unsets, Shell, ENV_VARS};
+
+/// Returns the shell code that wraps aws-creds in a function, so that `aws-creds use [profile]` and
+/// `aws-creds clear` can change the environment of the current shell. Meant to be evaluated from the
+/// shell's rc file.
+pub fn shell_init(shell: Shell) -> String {
+    let clear = format_unsets(shell, ENV_VARS);
+    match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            r#"aws-creds() {{
+    case "$1" in
+        use)
+            shift
+            case "$1" in
+                ""|-*) ;;
+                *) set -- --profile "$@" ;;
+            esac
+            local __aws_creds_env
+            __aws_creds_env="$(command aws-creds show --shell {shell} "$@")" && eval "$__aws_creds_env"
+            ;;
+        clear)
+            {clear}
+            ;;
+        *)
+            command aws-creds "$@"
+            ;;
+    esac
+}}
+
+aws_creds_prompt() {{
+    command aws-creds prompt 2>/dev/null
+}}"#,
+            shell = if shell == Shell::Zsh { "zsh" } else { "bash" },
+            clear = clear,
+        ),
+        Shell::Fish => format!(
+            r#"function aws-creds --description 'aws-creds, with `use` and `clear` changing the current shell'
+    switch "$argv[1]"
+        case use
+            set -l args $argv[2..-1]
+            if test (count $args) -gt 0; and not string match -q -- '-*' $args[1]
+                set args --profile $args
+            end
+            set -l env (command aws-creds show --shell fish $args)
+            and printf '%s\n' $env | source
+        case clear
+            {clear}
+        case '*'
+            command aws-creds $argv
+    end
+end
+
+function aws_creds_prompt
+    command aws-creds prompt 2>/dev/null
+end"#,
+            clear = clear,
+        ),
+    }
+}
\ No newline at end of file
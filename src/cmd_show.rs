@@ -1,38 +1,230 @@
+use std::io::IsTerminal;
 use anyhow::anyhow;
-use crate::utils::{check_config_path, get_temp_cred_status, parse_creds, parse_profile_name, TempCredStatus};
+use crate::{
+    audit,
+    cmd_get,
+    shell::{format_exports, format_unsets, get_stale_vars, ExtraVar, Shell, PROFILE_VAR},
+    types::TemporaryCredentials,
+    utils::{
+        check_config_path, format_duration, get_profile_region, get_refresh_threshold,
+        get_remaining_lifetime, get_temp_cred_status, mark_profile_used, parse_creds, parse_duration,
+        resolve_profile_name, write_creds, TempCredStatus,
+    },
+};
 
-pub fn show_creds(profile_name: &Option<String>, config_path: &Option<String>) -> Result<String, anyhow::Error> {
+pub fn show_creds(
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+    min_lifetime: &Option<String>,
+    strict: bool,
+    passthrough: bool,
+    shell: &Option<Shell>,
+    extra: &[ExtraVar],
+) -> Result<String, anyhow::Error> {
+    let (name, profile_creds) = load_temp_creds(profile_name, config_path, min_lifetime, strict, passthrough)?;
+    record_export(config_path, "show", &name, &profile_creds, &[])?;
+    let mut vars = get_env_vars(&profile_creds);
+
+    // Statements for a shell always mark the profile, which `prompt` reads, and unset what's left
+    // over from an earlier profile
+    if let Some(shell) = shell {
+        let mut extra = extra.to_vec();
+        if !extra.contains(&ExtraVar::Profile) {
+            extra.push(ExtraVar::Profile);
+        }
+
+        vars.extend(get_extra_env_vars(&name, &profile_creds, &extra, config_path)?);
+
+        let mut output = format_exports(*shell, &vars);
+        let unset = get_stale_vars(&vars, &extra);
+
+        if !unset.is_empty() {
+            output.push('\n');
+            output.push_str(&format_unsets(*shell, &unset));
+        }
+
+        return Ok(output);
+    }
+
+    // Plain `KEY=value` pairs can't unset anything, so an earlier session token would be paired
+    // with the permanent key
+    if passthrough {
+        eprintln!("NOTE: run `eval \"$(aws-creds unset)\"` first if another profile's credentials are exported in this shell, or use `--shell`, which unsets them.");
+    }
+
+    vars.extend(get_extra_env_vars(&name, &profile_creds, extra, config_path)?);
+    Ok(
+        vars
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join(" ")
+    )
+}
+
+/// Returns the environment variables that expose the temporary credentials. The session token is
+/// left out when there is none, i.e. for passed through permanent credentials.
+pub fn get_env_vars(profile_creds: &TemporaryCredentials) -> Vec<(&'static str, String)> {
+    let mut env_vars = vec![
+        ("AWS_ACCESS_KEY_ID", profile_creds.access_key_id.to_owned()),
+        ("AWS_SECRET_ACCESS_KEY", profile_creds.secret_access_key.to_owned()),
+    ];
+
+    if !profile_creds.session_token.is_empty() {
+        env_vars.push(("AWS_SESSION_TOKEN", profile_creds.session_token.to_owned()));
+    }
+
+    env_vars
+}
+
+/// Records in the audit log that a profile's credentials were handed out, e.g. by `show` or `exec`.
+/// The command passed to `exec` is left out of the record.
+pub fn record_export(
+    config_path: &Option<String>,
+    event: &str,
+    name: &str,
+    profile_creds: &TemporaryCredentials,
+    trailing_command: &[String],
+) -> Result<(), anyhow::Error> {
+    let path = check_config_path(config_path)?;
+    let audit_event = audit::new_event(event, name, &profile_creds.expiration, trailing_command);
+    audit::record_event(&parse_creds(&path)?, &path, audit_event)
+}
+
+/// Returns the extra variables for a profile's credentials, in the order of `ENV_VARS`. The region
+/// and expiration are left out when they're empty, e.g. for passed through permanent credentials.
+pub fn get_extra_env_vars(
+    name: &str,
+    profile_creds: &TemporaryCredentials,
+    extra: &[ExtraVar],
+    config_path: &Option<String>,
+) -> Result<Vec<(&'static str, String)>, anyhow::Error> {
+    let mut env_vars = vec![];
+    if extra.contains(&ExtraVar::Region) {
+        let all_creds = parse_creds(&check_config_path(config_path)?)?;
+        let region = get_profile_region(&all_creds, name)?;
+        if !region.is_empty() {
+            env_vars.push(("AWS_REGION", region.to_owned()));
+            env_vars.push(("AWS_DEFAULT_REGION", region));
+        }
+    }
+
+    if extra.contains(&ExtraVar::Expiration) && !profile_creds.expiration.is_empty() {
+        env_vars.push(("AWS_CREDENTIAL_EXPIRATION", profile_creds.expiration.to_owned()));
+    }
+
+    if extra.contains(&ExtraVar::Profile) {
+        env_vars.push((PROFILE_VAR, name.to_string()));
+    }
+
+    Ok(env_vars)
+}
+
+/// Returns the name and temporary credentials of a profile. If the credentials will expire within
+/// `min_lifetime` (or the profile's refresh threshold when no lifetime is passed), the user is asked
+/// whether to refresh them first, unless `strict` is set, in which case an error is returned. Without
+/// a terminal to ask on, a warning is printed and the still valid credentials are returned.
+/// With `passthrough`, the permanent access key pair is returned instead, without a session token.
+pub fn load_temp_creds(
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+    min_lifetime: &Option<String>,
+    strict: bool,
+    passthrough: bool,
+) -> Result<(String, TemporaryCredentials), anyhow::Error> {
     let path = check_config_path(config_path)?;
-    let all_creds = parse_creds(&path)?;
-    let name = parse_profile_name(profile_name, &all_creds.default)?;
-    let profile_creds = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
-        Some(p) => &p.temporary_credentials,
+    let mut all_creds = parse_creds(&path)?;
+    let name = resolve_profile_name(profile_name, &all_creds)?;
+
+    // Usage is only tracked for the interactive picker, to avoid rewriting the config file on every run
+    if all_creds.interactive_picker {
+        mark_profile_used(&mut all_creds, &name);
+        write_creds(&all_creds, &path)?;
+    }
+
+    let profile = match all_creds.profiles.iter().find(|p| p.profile_name == name) {
+        Some(p) => p,
         _ => return Err(anyhow!("profile `{}` doesn't exist", name)),
     };
 
-    match get_temp_cred_status(&profile_creds.expiration)? {
+    if passthrough {
+        if profile.web_identity.is_some() {
+            return Err(anyhow!("profile `{}` assumes a role with a web identity and has no permanent access key to pass through", name));
+        }
+
+        eprintln!(
+            "WARNING: passing through the permanent access key of profile `{}`. These credentials don't expire and aren't protected by MFA, so keep them out of logs and shared environments.",
+            name,
+        );
+
+        let perm_creds = &profile.permanent_credentials;
+        return Ok((name.to_owned(), TemporaryCredentials {
+            access_key_id: perm_creds.access_key_id.to_owned(),
+            secret_access_key: perm_creds.secret_access_key.to_owned(),
+            session_token: "".to_string(),
+            expiration: "".to_string(),
+        }));
+    }
+
+    let required_lifetime = match min_lifetime {
+        Some(l) => parse_duration(l)?,
+        None => get_refresh_threshold(&all_creds, profile)?,
+    };
+
+    let profile_creds = &profile.temporary_credentials;
+    match get_temp_cred_status(&profile_creds.expiration, required_lifetime)? {
         TempCredStatus::Empty => {
                 Err(anyhow!("the temporary credentials for profile `{}` haven't yet been retrieved\nPlease run `aws-creds get` to fix", name))
         },
         TempCredStatus::Expired => {
                 Err(anyhow!("the temporary credentials for profile `{}` have expired\nPlease run `aws-creds get` to fix", name))
         },
-        TempCredStatus::Ok => {
-            Ok(
-                format!(
-                    "AWS_ACCESS_KEY_ID={} AWS_SECRET_ACCESS_KEY={} AWS_SESSION_TOKEN={}",
-                    profile_creds.access_key_id,
-                    profile_creds.secret_access_key,
-                    profile_creds.session_token
-                )
-            )
-        }
+        TempCredStatus::ExpiringSoon => {
+            let remaining = get_remaining_lifetime(&profile_creds.expiration)?.unwrap_or_default();
+            if strict {
+                return Err(anyhow!(
+                    "the temporary credentials for profile `{}` expire in {}, which is less than the required {}\nPlease run `aws-creds get` to fix",
+                    name,
+                    format_duration(remaining),
+                    format_duration(required_lifetime),
+                ));
+            }
+
+            if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+                eprintln!(
+                    "WARNING: the temporary credentials for profile `{}` expire in {}. Run `aws-creds get` to refresh them.",
+                    name,
+                    format_duration(remaining),
+                );
+
+                return Ok((name, profile_creds.to_owned()));
+            }
+
+            let refresh = inquire::Confirm::new(&format!("The temporary credentials for profile `{}` expire in {}. Would you like to refresh them now?", name, format_duration(remaining)))
+                .with_default(true)
+                .prompt()
+                .map_err(|_| anyhow!("failed to get user confirmation for refresh"))?;
+
+            if !refresh {
+                return Ok((name, profile_creds.to_owned()));
+            }
+
+            cmd_get::get_new_creds(&[name.to_owned()], &[], false, &None, &Some(path.to_owned()))?;
+
+            let all_creds = parse_creds(&path)?;
+            match all_creds.profiles.into_iter().find(|p| p.profile_name == name) {
+                Some(p) => Ok((name, p.temporary_credentials)),
+                _ => Err(anyhow!("profile `{}` doesn't exist", name)),
+            }
+        },
+        TempCredStatus::Ok => Ok((name, profile_creds.to_owned())),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use clap::ValueEnum;
     use core::panic;
 
     #[test]
@@ -49,7 +241,7 @@ mod test {
             "AWS_ACCESS_KEY_ID=test1_temp_access_key_id AWS_SECRET_ACCESS_KEY=test1_temp_secret_access_key AWS_SESSION_TOKEN=test1_temp_session_token";
 
         // No profile passed
-        match show_creds(&None, &config_path) {
+        match show_creds(&None, &config_path, &None, true, false, &None, &[]) {
             Ok(r) => {
                 assert_eq!(r, correct_response);
             },
@@ -57,7 +249,7 @@ mod test {
         };
 
         // Profile passed
-        match show_creds(&Some("test1".to_string()), &config_path) {
+        match show_creds(&Some("test1".to_string()), &config_path, &None, true, false, &None, &[]) {
             Ok(r) => {
                 assert_eq!(r, correct_response);
             },
@@ -66,8 +258,48 @@ mod test {
             }
         }
 
+        // Required lifetime exceeds the remaining lifetime
+        match show_creds(&None, &config_path, &Some("36500d".to_string()), true, false, &None, &[]) {
+            Ok(_) => panic!("This should not have passed"),
+            Err(e) => {
+                assert!(e.to_string().contains("which is less than the required"));
+            },
+        };
+
+        // Permanent credentials passed through
+        match show_creds(&Some("test1".to_string()), &config_path, &None, true, true, &None, &[]) {
+            Ok(r) => {
+                assert_eq!(r, "AWS_ACCESS_KEY_ID=not_used AWS_SECRET_ACCESS_KEY=not_used");
+            },
+            Err(e) => panic!("{}", e),
+        };
+
+        // Statements for a shell
+        match show_creds(&Some("test1".to_string()), &config_path, &None, true, false, &Some(Shell::Fish), &[]) {
+            Ok(r) => {
+                assert_eq!(r, "set -gx AWS_ACCESS_KEY_ID 'test1_temp_access_key_id'\nset -gx AWS_SECRET_ACCESS_KEY 'test1_temp_secret_access_key'\nset -gx AWS_SESSION_TOKEN 'test1_temp_session_token'\nset -gx AWS_CREDS_PROFILE 'test1'");
+            },
+            Err(e) => panic!("{}", e),
+        };
+
+        // Extra variables
+        match show_creds(&Some("test1".to_string()), &config_path, &None, true, false, &None, ExtraVar::value_variants()) {
+            Ok(r) => {
+                assert_eq!(r, format!("{} AWS_REGION=not_used AWS_DEFAULT_REGION=not_used AWS_CREDENTIAL_EXPIRATION=2100-03-21T12:00:00Z AWS_CREDS_PROFILE=test1", correct_response));
+            },
+            Err(e) => panic!("{}", e),
+        };
+
+        // Statements for a shell with only some extra variables
+        match show_creds(&Some("test1".to_string()), &config_path, &None, true, true, &Some(Shell::Bash), &[ExtraVar::Region]) {
+            Ok(r) => {
+                assert_eq!(r, "export AWS_ACCESS_KEY_ID='not_used'\nexport AWS_SECRET_ACCESS_KEY='not_used'\nexport AWS_REGION='not_used'\nexport AWS_DEFAULT_REGION='not_used'\nexport AWS_CREDS_PROFILE='test1'\nunset AWS_SESSION_TOKEN");
+            },
+            Err(e) => panic!("{}", e),
+        };
+
         // Non-existent path
-        match show_creds(&None, &Some("/i_dont_exist".to_string())) {
+        match show_creds(&None, &Some("/i_dont_exist".to_string()), &None, true, false, &None, &[]) {
             Ok(_) => {
                 panic!("This should not have passed");
             },
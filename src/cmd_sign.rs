@@ -0,0 +1,163 @@
+use std::{fs, time::SystemTime};
+use anyhow::{anyhow, Error};
+use crate::{
+    cmd_show::load_temp_creds,
+    sigv4::sign_request,
+    utils::{check_config_path, get_profile_region, parse_creds},
+};
+
+/// Signs an HTTP request with a profile's temporary credentials. The signed headers are printed, or
+/// with `send`, the request is sent and the response body is printed (with the status and headers
+/// on stderr).
+#[allow(clippy::too_many_arguments)]
+pub fn sign_http_request(
+    method: &str,
+    url: &str,
+    headers: &[String],
+    data: &Option<String>,
+    service: &Option<String>,
+    region: &Option<String>,
+    send: bool,
+    profile_name: &Option<String>,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    let (name, temp_creds) = load_temp_creds(profile_name, config_path, &None, false, false)?;
+    let region = match region {
+        Some(r) => r.to_owned(),
+        None => get_profile_region(&parse_creds(&check_config_path(config_path)?)?, &name)?,
+    };
+
+    let service = match service {
+        Some(s) => s.to_owned(),
+        None => get_service_from_url(url).ok_or_else(|| anyhow!("failed to guess the service from `{}`; please pass --service", url))?,
+    };
+
+    let headers = headers.iter().map(|h| parse_header(h)).collect::<Result<Vec<(String, String)>, Error>>()?;
+    let body = match data.as_deref() {
+        Some(d) if d.starts_with('@') => fs::read(&d[1..]).map_err(|e| anyhow!("failed to read `{}`: {}", &d[1..], e))?,
+        Some(d) => d.as_bytes().to_vec(),
+        None => Vec::new(),
+    };
+
+    let method = method.to_uppercase();
+    let header_refs: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let signed_headers = sign_request(&temp_creds, &region, &service, &method, url, &header_refs, &body, SystemTime::now())?;
+
+    if !send {
+        for (k, v) in headers.iter().chain(signed_headers.iter()) {
+            println!("{}: {}", k, v);
+        }
+
+        return Ok(());
+    }
+
+    let all_headers: Vec<&(String, String)> = headers.iter().chain(signed_headers.iter()).collect();
+    send_request(&method, url, &all_headers, body)
+}
+
+/// Sends the signed request and prints the response, exiting with 1 if it wasn't successful. The
+/// credentials are loaded before this, since refreshing them starts a runtime of its own.
+#[::tokio::main]
+async fn send_request(method: &str, url: &str, headers: &[&(String, String)], body: Vec<u8>) -> Result<(), Error> {
+    let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|_| anyhow!("the method `{}` is invalid", method))?;
+    let mut request = reqwest::Client::new().request(method, url).body(body);
+    for (k, v) in headers {
+        request = request.header(k, v);
+    }
+
+    let response = request.send().await.map_err(|e| anyhow!("failed to send request: {}", e))?;
+    eprintln!("{:?} {}", response.version(), response.status());
+    for (k, v) in response.headers() {
+        eprintln!("{}: {}", k, v.to_str().unwrap_or("<binary>"));
+    }
+    eprintln!();
+
+    let status = response.status();
+    println!("{}", response.text().await.map_err(|e| anyhow!("failed to read response: {}", e))?);
+
+    if !status.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Parses a `Name: value` header
+fn parse_header(header: &str) -> Result<(String, String), Error> {
+    match header.split_once(':') {
+        Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_lowercase(), v.trim().to_string())),
+        _ => Err(anyhow!("the header `{}` is invalid (expected `Name: value`)", header)),
+    }
+}
+
+/// Guesses the signing name of the service from the endpoint's host name
+fn get_service_from_url(url: &str) -> Option<String> {
+    let host = url.split("://").nth(1)?.split(['/', ':', '?']).next()?;
+    let labels: Vec<&str> = host.split('.').collect();
+
+    // Function URLs aren't on amazonaws.com, e.g. abc123.lambda-url.us-east-1.on.aws
+    if host.ends_with(".on.aws") && labels.get(1) == Some(&"lambda-url") {
+        return Some("lambda".to_string());
+    }
+
+    let domain_labels = if host.ends_with(".amazonaws.com") {
+        2
+    } else if host.ends_with(".amazonaws.com.cn") {
+        3
+    } else {
+        return None;
+    };
+
+    // S3's host names start with the bucket (or access point), which may contain dots, e.g.
+    // my.bucket.s3.us-east-1.amazonaws.com, so they're matched from the end
+    let endpoint = &labels[..labels.len() - domain_labels];
+    match endpoint {
+        [.., "s3" | "s3-accesspoint", "dualstack", _]
+        | [.., "s3" | "s3-accesspoint", _]
+        | [.., "s3"] => return Some("s3".to_string()),
+        // e.g. mybucket.s3-us-west-2.amazonaws.com
+        [.., legacy] if legacy.starts_with("s3-") => return Some("s3".to_string()),
+        _ => {},
+    }
+
+    match labels.as_slice() {
+        // e.g. abc123.execute-api.us-east-1.amazonaws.com or search-x.us-east-1.es.amazonaws.com
+        [_, "execute-api", ..] => Some("execute-api".to_string()),
+        [_, _, "es", ..] => Some("es".to_string()),
+        [_, _, "aoss", ..] => Some("aoss".to_string()),
+        // e.g. sts.us-east-1.amazonaws.com
+        [service, ..] => Some(service.to_string()),
+        [] => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_service_from_url() {
+        assert_eq!(get_service_from_url("https://abc123.execute-api.us-east-1.amazonaws.com/prod/items"), Some("execute-api".to_string()));
+        assert_eq!(get_service_from_url("https://search-logs-abc.eu-west-1.es.amazonaws.com/_search"), Some("es".to_string()));
+        assert_eq!(get_service_from_url("https://abc.us-east-1.aoss.amazonaws.com"), Some("aoss".to_string()));
+        assert_eq!(get_service_from_url("https://abc.lambda-url.us-east-1.on.aws/"), Some("lambda".to_string()));
+        assert_eq!(get_service_from_url("https://sts.us-east-1.amazonaws.com/?Action=GetCallerIdentity"), Some("sts".to_string()));
+        assert_eq!(get_service_from_url("https://mybucket.s3.us-east-1.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://my.dotted.bucket.s3.eu-west-1.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://mybucket.s3.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://mybucket.s3-us-west-2.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://mybucket.s3.dualstack.us-east-1.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://ap-123456789012.s3-accesspoint.us-east-1.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://ap-123456789012.s3-accesspoint.dualstack.us-east-1.amazonaws.com/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://mybucket.s3.cn-north-1.amazonaws.com.cn/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://s3.us-east-1.amazonaws.com/mybucket/key"), Some("s3".to_string()));
+        assert_eq!(get_service_from_url("https://example.com/"), None);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(parse_header("Content-Type: application/json").unwrap(), ("content-type".to_string(), "application/json".to_string()));
+        assert!(parse_header("no colon").is_err());
+        assert!(parse_header(": value").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use anyhow::anyhow;
+use crate::utils::{check_config_path, parse_creds, write_creds};
+
+pub fn tag_profile(profile_name: &str, tags: &[String], config_path: &Option<String>) -> Result<(), anyhow::Error> {
+    for t in tags {
+        if t.trim().is_empty() || t.contains(|c: char| c.is_whitespace() || c == ',') {
+            return Err(anyhow!("`{}` is not a valid tag (tags cannot be empty or contain whitespace or commas)", t));
+        }
+    }
+
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let profile = match all_creds.profiles.iter_mut().find(|p| p.profile_name == profile_name) {
+        Some(p) => p,
+        None => return Err(anyhow!("profile `{}` doesn't exist", profile_name)),
+    };
+
+    for t in tags {
+        if !profile.tags.contains(t) {
+            profile.tags.push(t.to_owned());
+        }
+    }
+    profile.tags.sort();
+
+    write_creds(&all_creds, &path)
+}
+
+pub fn untag_profile(profile_name: &str, tags: &[String], config_path: &Option<String>) -> Result<(), anyhow::Error> {
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let profile = match all_creds.profiles.iter_mut().find(|p| p.profile_name == profile_name) {
+        Some(p) => p,
+        None => return Err(anyhow!("profile `{}` doesn't exist", profile_name)),
+    };
+
+    profile.tags.retain(|t| !tags.contains(t));
+
+    write_creds(&all_creds, &path)
+}
\ No newline at end of file
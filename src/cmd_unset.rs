@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Error};
+use clap::ValueEnum;
+use crate::{
+    cmd_show::{get_env_vars, get_extra_env_vars},
+    shell::{format_unsets, ExtraVar, Shell, ENV_VARS, PROFILE_VAR},
+    utils::{check_config_path, parse_creds, resolve_profile_name},
+};
+
+/// Returns the statements that unset every variable aws-creds may have set in a shell
+pub fn unset_vars(shell: &Option<Shell>) -> String {
+    format_unsets(shell.unwrap_or_else(Shell::detect), ENV_VARS)
+}
+
+/// Compares the variables in the environment with the ones a profile's stored credentials would set,
+/// one line per variable. Values are masked, since the output may end up in a terminal's scrollback.
+/// The profile defaults to the one marked in the environment.
+pub fn diff_env(profile_name: &Option<String>, config_path: &Option<String>) -> Result<String, Error> {
+    let path = check_config_path(config_path)?;
+    let all_creds = parse_creds(&path)?;
+    let name = match (profile_name, std::env::var(PROFILE_VAR)) {
+        (None, Ok(n)) if !n.is_empty() => n,
+        _ => resolve_profile_name(profile_name, &all_creds)?,
+    };
+
+    let profile = all_creds.profiles
+        .iter()
+        .find(|p| p.profile_name == name)
+        .ok_or_else(|| anyhow!("profile `{}` doesn't exist", name))?;
+
+    let mut expected = get_env_vars(&profile.temporary_credentials);
+    expected.extend(get_extra_env_vars(&name, &profile.temporary_credentials, ExtraVar::value_variants(), config_path)?);
+
+    let lines: Vec<String> = ENV_VARS
+        .iter()
+        .map(|var| {
+            let current = std::env::var(var).ok();
+            let stored = expected.iter().find(|(k, _)| k == var).map(|(_, v)| v.to_owned());
+            format_diff_line(var, &current, &stored, &name)
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+/// Formats one variable of the diff: `=` when the environment matches the profile, `~` when it
+/// differs, `+` when it's only set in the environment and `-` when it's only set by the profile
+fn format_diff_line(var: &str, current: &Option<String>, stored: &Option<String>, name: &str) -> String {
+    match (current, stored) {
+        (Some(c), Some(s)) if c == s => format!("= {} {}", var, mask(c)),
+        (Some(c), Some(s)) => format!("~ {} {} (environment), {} (profile `{}`)", var, mask(c), mask(s), name),
+        (Some(c), None) => format!("+ {} {} (environment), not set (profile `{}`)", var, mask(c), name),
+        (None, Some(s)) => format!("- {} not set (environment), {} (profile `{}`)", var, mask(s), name),
+        (None, None) => format!("= {} not set", var),
+    }
+}
+
+/// Masks a value, keeping its first and last four characters when it's long enough to be told apart
+fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() < 12 {
+        return "****".to_string();
+    }
+
+    format!(
+        "{}...{}",
+        chars[..4].iter().collect::<String>(),
+        chars[chars.len() - 4..].iter().collect::<String>(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_diff_line() {
+        let key = Some("ASIAABCDEFGHWXYZ".to_string());
+        let other_key = Some("ASIAZZZZZZZZ1234".to_string());
+
+        assert_eq!(format_diff_line("AWS_ACCESS_KEY_ID", &key, &key, "prod"), "= AWS_ACCESS_KEY_ID ASIA...WXYZ");
+        assert_eq!(format_diff_line("AWS_ACCESS_KEY_ID", &key, &other_key, "prod"), "~ AWS_ACCESS_KEY_ID ASIA...WXYZ (environment), ASIA...1234 (profile `prod`)");
+        assert_eq!(format_diff_line("AWS_SESSION_TOKEN", &key, &None, "prod"), "+ AWS_SESSION_TOKEN ASIA...WXYZ (environment), not set (profile `prod`)");
+        assert_eq!(format_diff_line("AWS_CREDS_PROFILE", &None, &Some("prod".to_string()), "prod"), "- AWS_CREDS_PROFILE not set (environment), **** (profile `prod`)");
+        assert_eq!(format_diff_line("AWS_SESSION_TOKEN", &None, &None, "prod"), "= AWS_SESSION_TOKEN not set");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Error};
+use inquire::{self, validator::Validation};
+use crate::{
+    types::MfaDevice,
+    utils::{check_config_path, get_mfa_device_label, parse_creds, upsert_profile, write_creds},
+};
+
+/// Replaces individual permanent credential fields of a profile, keeping the rest
+pub fn update_profile(
+    profile_name: &str,
+    access_key: bool,
+    secret_access_key: bool,
+    mfa_serial_number: &Option<String>,
+    region: &Option<String>,
+    config_path: &Option<String>,
+) -> Result<(), Error> {
+    // Ensures that the inquire::<String> methods below won't accept a blank string
+    let string_validator = |input: &str| {
+        if input.trim().is_empty() {
+            Ok(Validation::Invalid("field cannot be empty".into()))
+        } else {
+            Ok(Validation::Valid)
+        }
+    };
+
+    let path = check_config_path(config_path)?;
+    let mut all_creds = parse_creds(&path)?;
+    let mut profile = match all_creds.profiles.iter().find(|p| p.profile_name == profile_name) {
+        Some(p) => p.to_owned(),
+        None => return Err(anyhow!("profile `{}` doesn't exist", profile_name)),
+    };
+
+    let perm_creds = &mut profile.permanent_credentials;
+
+    if access_key {
+        perm_creds.access_key_id = inquire::Password::new("AWS_ACCESS_KEY_ID:")
+            .without_confirmation()
+            .with_validator(string_validator)
+            .prompt()
+            .map_err(|_| anyhow!("failed to get user input for access_key_id"))?;
+
+        // The creation date of a key entered by hand is unknown
+        perm_creds.access_key_created = None;
+    }
+
+    // A new access key always comes with a new secret
+    if access_key || secret_access_key {
+        perm_creds.secret_access_key = inquire::Password::new("AWS_SECRET_ACCESS_KEY:")
+            .without_confirmation()
+            .with_validator(string_validator)
+            .prompt()
+            .map_err(|_| anyhow!("failed to get user input for secret_access_key"))?;
+    }
+
+    if let Some(s) = mfa_serial_number {
+        perm_creds.mfa_devices = vec![MfaDevice {
+            label: get_mfa_device_label(s),
+            serial_number: s.to_owned(),
+            totp_seed: None,
+        }];
+        perm_creds.last_mfa_device = None;
+    }
+
+    if let Some(r) = region {
+        perm_creds.region = r.to_owned();
+    }
+
+    upsert_profile(&mut all_creds, profile);
+    write_creds(&all_creds, &path)?;
+
+    println!("Profile `{}` updated", profile_name);
+    Ok(())
+}
\ No newline at end of file
@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use std::path::Path;
+use clap::{ArgGroup, Parser, Subcommand};
+use shell::{ExtraVar, Shell};
 mod cmd_show;
 mod cmd_remove;
 mod cmd_new;
@@ -6,8 +8,33 @@ mod cmd_default;
 mod cmd_get;
 mod cmd_rename;
 mod cmd_list;
+mod cmd_exec;
+mod cmd_tag;
+mod cmd_config;
+mod cmd_doctor;
+mod cmd_update;
+mod cmd_rotate;
+mod cmd_mfa;
+mod cmd_console;
+mod cmd_eks;
+mod cmd_sign;
+mod cmd_presign;
+mod cmd_git_credential;
+mod cmd_docker_credential;
+mod cmd_shell_init;
+mod cmd_prompt;
+mod cmd_shell;
+mod cmd_unset;
+mod cmd_history;
+mod audit;
 mod utils;
 mod types;
+mod totp;
+mod sigv4;
+mod shell;
+
+/// The name Docker runs the credential helper as, when aws-creds is linked under it
+const DOCKER_CREDENTIAL_HELPER_NAME: &str = "docker-credential-aws-creds";
 
 #[derive(Parser)]
 #[command(version, about = "Makes it easy to manage and use temporary AWS credentials")]
@@ -16,6 +43,71 @@ struct Cli {
     command: Option<Commands>,
 }
 
+#[derive(Subcommand)]
+enum ConfigCommands {
+    #[command(about = "Print the path to the config file and where it came from")]
+    #[command(long_about = "Print the path to the config file and where it came from. Without --config, the path comes from the nearest .aws-creds.json project file, then the AWS_CREDS_CONFIG environment variable, then $XDG_CONFIG_HOME/aws-creds/creds.json, then ~/.config/aws-creds/creds.json")]
+    Path {
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MfaCommands {
+    #[command(about = "Create and enable a virtual MFA device for a profile's IAM user")]
+    #[command(long_about = "Create and enable a virtual MFA device for a profile's IAM user. The device's key is shown as a QR code for an authenticator app, and can optionally be stored so that aws-creds generates the MFA codes itself")]
+    Enroll {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "The name of the virtual MFA device (defaults to the IAM user name)")]
+        device_name: Option<String>,
+
+        #[arg(long, help = "Store the device's key and generate MFA codes automatically")]
+        store_seed: bool,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Add an existing MFA device to a profile")]
+    Add {
+        /// The serial number of the MFA device
+        serial_number: String,
+
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "The label of the device (defaults to the device name)")]
+        label: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(visible_alias = "rm", about = "Remove an MFA device from a profile")]
+    Remove {
+        /// The label of the MFA device
+        label: String,
+
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(visible_alias = "ls", about = "Print the MFA devices of a profile")]
+    List {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Create a new profile")]
@@ -23,10 +115,51 @@ enum Commands {
         /// The name of the profile
         profile: String,
 
+        #[arg(long, help = "Assume a role with an OIDC token (e.g. from CI or Kubernetes) instead of using an access key")]
+        web_identity: bool,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Update the permanent credentials of a profile")]
+    #[command(group(ArgGroup::new("fields").required(true).multiple(true)))]
+    Update {
+        /// The name of the profile
+        profile: String,
+
+        #[arg(long, group = "fields", help = "Prompt for a new access key ID and secret access key")]
+        access_key: bool,
+
+        #[arg(long, group = "fields", help = "Prompt for a new secret access key")]
+        secret_access_key: bool,
+
+        #[arg(long, group = "fields", help = "Replace the MFA devices with the device with this serial number")]
+        mfa_serial_number: Option<String>,
+
+        #[arg(long, group = "fields", help = "The new AWS region")]
+        region: Option<String>,
+
         #[arg(short, long, help = "Path to the config file")]
         config: Option<String>,
     },
 
+    #[command(about = "Replace the access key of a profile with a new one")]
+    #[command(long_about = "Replace the access key of a profile with a new one. A new key is created and verified, saved to the profile, and then the old key is deactivated and deleted")]
+    Rotate {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Manage MFA devices")]
+    Mfa {
+        #[command(subcommand)]
+        command: MfaCommands,
+    },
+
     #[command(about = "Print temporary credentials formatted as environment variables")]
     Show {
         #[arg(short, long, help = "The name of the AWS profile")]
@@ -34,10 +167,144 @@ enum Commands {
 
         #[arg(short, long, help = "Path to the config file")]
         config: Option<String>,
+
+        #[arg(short, long, help = "The minimum remaining lifetime of the credentials (e.g. `30m`); defaults to the refresh threshold")]
+        min_lifetime: Option<String>,
+
+        #[arg(long, help = "Fail instead of prompting for a refresh when the credentials are expiring soon")]
+        strict: bool,
+
+        #[arg(long, conflicts_with_all = ["min_lifetime", "strict"], help = "Use the permanent access key pair instead of temporary credentials (e.g. for service accounts without MFA)")]
+        passthrough: bool,
+
+        #[arg(long, value_enum, help = "Print statements that export the credentials in this shell")]
+        shell: Option<Shell>,
+
+        #[arg(long, value_enum, value_delimiter = ',', num_args = 0.., default_values = ["region", "expiration", "profile"], help = "The variables to set alongside the credentials, comma-separated; pass `--extra` alone to set none")]
+        extra: Vec<ExtraVar>,
     },
 
-    #[command(about = "Retrieve new temporary credentials from AWS")]
-    Get {
+    #[command(about = "Run a command with temporary credentials set as environment variables")]
+    Exec {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+
+        #[arg(short, long, help = "The minimum remaining lifetime of the credentials (e.g. `30m`); defaults to the refresh threshold")]
+        min_lifetime: Option<String>,
+
+        #[arg(long, help = "Fail instead of prompting for a refresh when the credentials are expiring soon")]
+        strict: bool,
+
+        #[arg(long, conflicts_with_all = ["min_lifetime", "strict"], help = "Use the permanent access key pair instead of temporary credentials (e.g. for service accounts without MFA)")]
+        passthrough: bool,
+
+        #[arg(long, value_enum, value_delimiter = ',', num_args = 0.., default_values = ["region", "expiration", "profile"], help = "The variables to set alongside the credentials, comma-separated; pass `--extra` alone to set none")]
+        extra: Vec<ExtraVar>,
+
+        /// The command to run
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+
+    #[command(name = "shell", about = "Start a subshell with a profile's temporary credentials")]
+    #[command(long_about = "Start `$SHELL` with a profile's temporary credentials, `AWS_CREDS_PROFILE` and the profile's name in its prompt. The credentials are gone once the subshell exits, and a warning is printed in it when they're about to expire. Opening a shell for a different profile inside one asks for confirmation first")]
+    Subshell {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+
+        #[arg(short, long, help = "The minimum remaining lifetime of the credentials (e.g. `30m`); defaults to the refresh threshold")]
+        min_lifetime: Option<String>,
+
+        #[arg(long, help = "Fail instead of prompting for a refresh when the credentials are expiring soon")]
+        strict: bool,
+    },
+
+    #[command(about = "Print a URL that signs into the AWS console with a profile's credentials")]
+    #[command(long_about = "Print a URL that signs into the AWS console with a profile's credentials. Web identity profiles use their role session, while other profiles get a federation token from their access key, since the console doesn't accept sessions from `get`")]
+    Console {
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "The console URL or path to open (e.g. `s3` or `ec2/home`)")]
+        destination: Option<String>,
+
+        #[arg(short, long, help = "Open the URL in the browser instead of printing it")]
+        open: bool,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Print a kubectl ExecCredential with a token for an EKS cluster")]
+    #[command(long_about = "Print a kubectl ExecCredential with a token for an EKS cluster, like `aws eks get-token` does. Point a kubeconfig's exec plugin at `aws-creds eks-token --cluster <name>` to use a profile's temporary credentials")]
+    EksToken {
+        #[arg(long, help = "The name of the EKS cluster")]
+        cluster: String,
+
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+
+        #[arg(short, long, help = "The minimum remaining lifetime of the credentials (e.g. `30m`); defaults to the refresh threshold")]
+        min_lifetime: Option<String>,
+
+        #[arg(long, help = "Prompt for a refresh when the credentials are expiring soon and kubectl passes a terminal through, instead of failing")]
+        interactive: bool,
+    },
+
+    #[command(visible_alias = "curl", about = "Sign an HTTP request with SigV4, and optionally send it")]
+    #[command(long_about = "Sign an HTTP request with SigV4 using a profile's temporary credentials, e.g. for API Gateway endpoints with IAM authorization or OpenSearch. The signed headers are printed, or with `--send`, the request is sent and the response is printed")]
+    Sign {
+        /// The HTTP method
+        method: String,
+
+        /// The URL of the request
+        url: String,
+
+        #[arg(short = 'H', long = "header", help = "A header to send and sign, as `Name: value` (can be repeated)")]
+        headers: Vec<String>,
+
+        #[arg(short, long, help = "The body of the request, or `@path` to read it from a file")]
+        data: Option<String>,
+
+        #[arg(short, long, help = "The signing name of the service (e.g. `execute-api` or `es`); guessed from the URL by default")]
+        service: Option<String>,
+
+        #[arg(short, long, help = "The region to sign for; defaults to the profile's region")]
+        region: Option<String>,
+
+        #[arg(long, help = "Send the request and print the response instead of printing the signed headers")]
+        send: bool,
+
+        #[arg(short, long, help = "The name of the AWS profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Print a presigned URL for an S3 object")]
+    Presign {
+        /// The S3 URI of the object (e.g. `s3://bucket/key`)
+        s3_uri: String,
+
+        #[arg(short, long, default_value = "1h", help = "How long the URL works for (at most 7d, and never longer than the temporary credentials)")]
+        expires: String,
+
+        #[arg(short, long, default_value = "GET", value_parser = ["GET", "PUT"], ignore_case = true, help = "GET to download the object, or PUT to upload it")]
+        method: String,
+
+        #[arg(short, long, help = "The region of the bucket; defaults to the profile's region")]
+        region: Option<String>,
+
         #[arg(short, long, help = "The name of the AWS profile")]
         profile: Option<String>,
 
@@ -45,6 +312,80 @@ enum Commands {
         config: Option<String>,
     },
 
+    #[command(about = "A git credential helper for CodeCommit")]
+    #[command(long_about = "A git credential helper for CodeCommit, which derives the password from a profile's temporary credentials. The profile is taken from `git_hosts` in the config file, and defaults to the default profile. Set it up with `git config --global credential.https://git-codecommit.<region>.amazonaws.com.helper '!aws-creds git-credential'` and `git config --global credential.https://git-codecommit.<region>.amazonaws.com.useHttpPath true`")]
+    GitCredential {
+        /// The operation passed by git
+        #[arg(value_parser = ["get", "store", "erase"])]
+        operation: String,
+
+        #[arg(short, long, help = "The name of the AWS profile, overriding `git_hosts`")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "A Docker credential helper for ECR")]
+    #[command(long_about = "A Docker credential helper for ECR, which retrieves authorization tokens with a profile's temporary credentials and caches them until they expire. The profile is taken from `docker_registries` in the config file, and defaults to the default profile. Docker runs it when aws-creds is linked as `docker-credential-aws-creds` and set as a `credHelpers` entry in `~/.docker/config.json`")]
+    DockerCredential {
+        /// The operation passed by Docker
+        #[arg(value_parser = ["get", "store", "erase", "list"])]
+        operation: String,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Print shell functions for switching profiles in the current shell")]
+    #[command(long_about = "Print shell functions for switching profiles in the current shell: `aws-creds use [profile]` exports a profile's temporary credentials, `aws-creds clear` unsets them, and `aws_creds_prompt` prints a prompt segment. Add `eval \"$(aws-creds shell-init bash)\"` to ~/.bashrc, `eval \"$(aws-creds shell-init zsh)\"` to ~/.zshrc, or `aws-creds shell-init fish | source` to ~/.config/fish/config.fish")]
+    ShellInit {
+        /// The shell to print functions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    #[command(visible_alias = "clear", about = "Print statements that unset the variables aws-creds sets")]
+    #[command(long_about = "Print statements that unset every variable aws-creds may have set, e.g. `eval \"$(aws-creds unset)\"` after `export $(aws-creds show)`. With `--diff`, print how the environment differs from a profile's stored credentials instead, with masked values")]
+    Unset {
+        #[arg(short, long, value_enum, help = "The shell to print statements for; defaults to the one in $SHELL")]
+        shell: Option<Shell>,
+
+        #[arg(long, conflicts_with = "shell", help = "Compare the environment with the profile's stored credentials instead")]
+        diff: bool,
+
+        #[arg(short, long, requires = "diff", help = "The name of the AWS profile to compare with; defaults to the one in the environment")]
+        profile: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Print the profile in the environment and its remaining session time, for shell prompts")]
+    Prompt {
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Retrieve new temporary credentials from AWS")]
+    #[command(long_about = "Retrieve new temporary credentials from AWS. Profiles that share an IAM user and MFA device only need one MFA code, and the resulting session is stored in each of them")]
+    Get {
+        #[arg(short, long, num_args = 1.., help = "The name(s) of the AWS profile(s)")]
+        profile: Vec<String>,
+
+        #[arg(short, long, conflicts_with_all = ["profile", "tag"], help = "Retrieve new temporary credentials for every profile")]
+        all: bool,
+
+        #[arg(short, long, visible_alias = "group", help = "Retrieve new temporary credentials for the profiles with this tag (can be repeated)")]
+        tag: Vec<String>,
+
+        #[arg(long, help = "The label or serial number of the MFA device to use")]
+        mfa_device: Option<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
     #[command(about = "Sets a profile as default")]
     Default {
         /// The name of the profile
@@ -57,7 +398,11 @@ enum Commands {
     #[command(visible_alias = "rm", about = "Deletes a profile")]
     Remove {
         /// The name of the profile
-        profile: String,
+        #[arg(required_unless_present = "tag", conflicts_with = "tag")]
+        profile: Option<String>,
+
+        #[arg(short, long, visible_alias = "group", help = "Delete the profiles with this tag (can be repeated)")]
+        tag: Vec<String>,
 
         #[arg(short, long, help = "Path to the config file")]
         config: Option<String>,
@@ -76,38 +421,194 @@ enum Commands {
     },
 
     #[command(visible_alias = "ls", about = "Print a list of all profile names")]
-    #[command(long_about ="Print a list of all profile names. The following are annotations that may be next to a profile name:\n- default: the default profile\n- expired: the temporary credentials for the profile have expired (fix with `aws-creds get`)\n- empty: the temporary credentials for the profile are empty (fix with `aws-creds get`)")]
+    #[command(long_about ="Print a list of all profile names. The following are annotations that may be next to a profile name:\n- default: the default profile\n- expired: the temporary credentials for the profile have expired (fix with `aws-creds get`)\n- expiring soon: the temporary credentials for the profile will expire within the refresh threshold (fix with `aws-creds get`)\n- empty: the temporary credentials for the profile are empty (fix with `aws-creds get`)\n- rotate key: the access key is more than 90 days old (fix with `aws-creds rotate`)")]
     List {
+        #[arg(short, long, visible_alias = "group", help = "Only list the profiles with this tag (can be repeated)")]
+        tag: Vec<String>,
+
         #[arg(short, long, help = "Path to the config file")]
         config: Option<String>,
-    }
+    },
+
+    #[command(about = "Check the config file for problems")]
+    #[command(long_about = "Check the config file for problems, such as malformed credentials, regions, MFA serial numbers and expirations, duplicate profiles, a missing default profile, loose file permissions and a wrong system clock")]
+    Doctor {
+        #[arg(long, help = "Fix the problems that can be fixed automatically")]
+        fix: bool,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Inspect the configuration")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    #[command(about = "Show the audit log of credential operations")]
+    #[command(long_about = "Show the audit log of credential operations, which `new`, `get`, `show`, `exec`, `remove` and `rename` append to `audit.jsonl` next to the config file. Each event records when it happened, the profile, the caller identity ARN, the session expiration and the command line, but never the credentials themselves")]
+    History {
+        #[arg(short, long, help = "Only show events for this profile")]
+        profile: Option<String>,
+
+        #[arg(short, long, value_delimiter = ',', value_parser = ["new", "get", "show", "exec", "remove", "rename"], help = "Only show these events, comma-separated")]
+        event: Vec<String>,
+
+        #[arg(short, long, help = "Only show events from this long ago (e.g. `7d`)")]
+        since: Option<String>,
+
+        #[arg(short = 'n', long, help = "Only show the most recent events")]
+        limit: Option<usize>,
+
+        #[arg(long, help = "Print the events as JSON lines")]
+        json: bool,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Adds tags to a profile")]
+    Tag {
+        /// The name of the profile
+        profile: String,
+
+        /// The tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
+
+    #[command(about = "Removes tags from a profile")]
+    Untag {
+        /// The name of the profile
+        profile: String,
+
+        /// The tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        #[arg(short, long, help = "Path to the config file")]
+        config: Option<String>,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>>{
-    let cli = Cli::parse();
+    // Docker runs credential helpers as `docker-credential-<name> <operation>`
+    let mut args: Vec<String> = std::env::args().collect();
+    let program = args.first().map(|a| Path::new(a).file_name().unwrap_or_default().to_string_lossy().to_string());
+    if program.as_deref() == Some(DOCKER_CREDENTIAL_HELPER_NAME) {
+        args.insert(1, "docker-credential".to_string());
+    }
+
+    let cli = Cli::parse_from(args);
     match &cli.command {
-        Some(Commands::Show {profile, config}) => {
-            let result = cmd_show::show_creds(profile, config)?;
-            return Ok(println!("{}", result));
+        Some(Commands::Show {profile, config, min_lifetime, strict, passthrough, shell, extra}) => {
+            let result = cmd_show::show_creds(profile, config, min_lifetime, *strict, *passthrough, shell, extra)?;
+            println!("{}", result);
+            Ok(())
+        },
+        Some(Commands::Exec {profile, config, min_lifetime, strict, passthrough, extra, command}) => {
+            Ok(cmd_exec::exec_command(profile, config, min_lifetime, *strict, *passthrough, extra, command)?)
+        },
+        Some(Commands::Subshell { profile, config, min_lifetime, strict }) => {
+            Ok(cmd_shell::spawn_shell(profile, config, min_lifetime, *strict)?)
+        },
+        Some(Commands::Console { profile, destination, open, config }) => {
+            Ok(cmd_console::open_console(profile, destination, *open, config)?)
+        },
+        Some(Commands::EksToken { cluster, profile, config, min_lifetime, interactive }) => {
+            let result = cmd_eks::eks_token(cluster, profile, config, min_lifetime, *interactive)?;
+            println!("{}", result);
+            Ok(())
+        },
+        Some(Commands::Sign { method, url, headers, data, service, region, send, profile, config }) => {
+            Ok(cmd_sign::sign_http_request(method, url, headers, data, service, region, *send, profile, config)?)
+        },
+        Some(Commands::Presign { s3_uri, expires, method, region, profile, config }) => {
+            let result = cmd_presign::presign_s3_url(s3_uri, expires, method, region, profile, config)?;
+            println!("{}", result);
+            Ok(())
+        },
+        Some(Commands::GitCredential { operation, profile, config }) => {
+            Ok(cmd_git_credential::git_credential(operation, profile, config)?)
+        },
+        Some(Commands::DockerCredential { operation, config }) => {
+            Ok(cmd_docker_credential::docker_credential(operation, config)?)
+        },
+        Some(Commands::ShellInit { shell }) => {
+            println!("{}", cmd_shell_init::shell_init(*shell));
+            Ok(())
+        },
+        Some(Commands::Unset { shell, diff, profile, config }) => {
+            let result = if *diff {
+                cmd_unset::diff_env(profile, config)?
+            } else {
+                cmd_unset::unset_vars(shell)
+            };
+            println!("{}", result);
+            Ok(())
+        },
+        Some(Commands::Prompt { config }) => {
+            let result = cmd_prompt::show_prompt(config);
+            if !result.is_empty() {
+                println!("{}", result);
+            }
+            Ok(())
         },
         Some(Commands::Default {profile, config}) => {
-            return Ok(cmd_default::set_default(profile, config)?);
+            Ok(cmd_default::set_default(profile, config)?)
+        },
+        Some(Commands::Remove {profile, tag, config}) => {
+            Ok(cmd_remove::remove_profile(profile, tag, config)?)
+        },
+        Some(Commands::New {profile, web_identity, config}) => {
+            Ok(cmd_new::create_profile(profile, *web_identity, config)?)
+        },
+        Some(Commands::Update { profile, access_key, secret_access_key, mfa_serial_number, region, config }) => {
+            Ok(cmd_update::update_profile(profile, *access_key, *secret_access_key, mfa_serial_number, region, config)?)
+        },
+        Some(Commands::Rotate { profile, config }) => {
+            Ok(cmd_rotate::rotate_access_key(profile, config)?)
+        },
+        Some(Commands::Mfa { command: MfaCommands::Enroll { profile, device_name, store_seed, config } }) => {
+            Ok(cmd_mfa::enroll_mfa_device(profile, device_name, *store_seed, config)?)
         },
-        Some(Commands::Remove {profile, config}) => {
-            return Ok(cmd_remove::remove_profile(profile, config)?);
+        Some(Commands::Mfa { command: MfaCommands::Add { serial_number, profile, label, config } }) => {
+            Ok(cmd_mfa::add_mfa_device(profile, serial_number, label, config)?)
         },
-        Some(Commands::New {profile, config}) => {
-            return Ok(cmd_new::create_profile(profile, config)?);
+        Some(Commands::Mfa { command: MfaCommands::Remove { label, profile, config } }) => {
+            Ok(cmd_mfa::remove_mfa_device(profile, label, config)?)
         },
-        Some(Commands::Get { profile, config }) => {
-            return Ok(cmd_get::get_new_creds(profile, config)?);
+        Some(Commands::Mfa { command: MfaCommands::List { profile, config } }) => {
+            Ok(cmd_mfa::list_mfa_devices(profile, config)?)
+        },
+        Some(Commands::Get { profile, tag, all, mfa_device, config }) => {
+            Ok(cmd_get::get_new_creds(profile, tag, *all, mfa_device, config)?)
         },
         Some(Commands::Rename { old_profile, new_profile, config }) => {
-            return Ok(cmd_rename::rename_profile(old_profile, new_profile, config)?);
+            Ok(cmd_rename::rename_profile(old_profile, new_profile, config)?)
+        },
+        Some(Commands::List { tag, config }) => {
+            Ok(cmd_list::list_profiles(tag, config)?)
+        },
+        Some(Commands::Doctor { fix, config }) => {
+            Ok(cmd_doctor::run_doctor(*fix, config)?)
+        },
+        Some(Commands::Config { command: ConfigCommands::Path { config } }) => {
+            Ok(cmd_config::show_config_path(config)?)
+        },
+        Some(Commands::History { profile, event, since, limit, json, config }) => {
+            Ok(cmd_history::show_history(profile, event, since, limit, *json, config)?)
+        },
+        Some(Commands::Tag { profile, tags, config }) => {
+            Ok(cmd_tag::tag_profile(profile, tags, config)?)
+        },
+        Some(Commands::Untag { profile, tags, config }) => {
+            Ok(cmd_tag::untag_profile(profile, tags, config)?)
         },
-        Some(Commands::List { config }) => {
-            return Ok(cmd_list::list_profiles(config)?);
-        }
         None => {
             eprintln!("ERROR: missing required arguments\nFor a list of options, run `aws-creds --help`");
             std::process::exit(1);
@@ -0,0 +1,113 @@
+use clap::ValueEnum;
+
+/// The shells that aws-creds can print statements for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// The variable that marks which profile the credentials in the environment belong to
+pub const PROFILE_VAR: &str = "AWS_CREDS_PROFILE";
+
+/// Every variable that aws-creds sets in a shell, which clearing the shell unsets
+pub const ENV_VARS: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_CREDENTIAL_EXPIRATION",
+    PROFILE_VAR,
+];
+
+/// The variables that can be emitted alongside the credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExtraVar {
+    /// `AWS_REGION` and `AWS_DEFAULT_REGION`, from the profile or the project file
+    Region,
+    /// `AWS_CREDENTIAL_EXPIRATION`, when the session expires
+    Expiration,
+    /// `AWS_CREDS_PROFILE`, the name of the profile
+    Profile,
+}
+
+impl ExtraVar {
+    /// Returns the names of the variables this option emits
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            ExtraVar::Region => &["AWS_REGION", "AWS_DEFAULT_REGION"],
+            ExtraVar::Expiration => &["AWS_CREDENTIAL_EXPIRATION"],
+            ExtraVar::Profile => &[PROFILE_VAR],
+        }
+    }
+}
+
+impl Shell {
+    /// Guesses the current shell from `$SHELL`, falling back to bash, whose statements sh also reads
+    pub fn detect() -> Shell {
+        let program = std::env::var("SHELL").unwrap_or_default();
+        match std::path::Path::new(&program).file_name().and_then(|n| n.to_str()) {
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            _ => Shell::Bash,
+        }
+    }
+}
+
+/// Returns the variables of `ENV_VARS` that would be left over from an earlier profile, i.e. the ones
+/// that `vars` doesn't set (e.g. the session token when passing through permanent credentials).
+/// Variables of extras that weren't selected are left alone, since the user may have set them.
+pub fn get_stale_vars(vars: &[(&str, String)], extra: &[ExtraVar]) -> Vec<&'static str> {
+    let unselected: Vec<&str> = ExtraVar::value_variants()
+        .iter()
+        .filter(|e| !extra.contains(e))
+        .flat_map(|e| e.names().iter().copied())
+        .collect();
+
+    ENV_VARS
+        .iter()
+        .copied()
+        .filter(|n| !unselected.contains(n) && !vars.iter().any(|(k, _)| k == n))
+        .collect()
+}
+
+/// Returns the statements that set environment variables in a shell, one per line
+pub fn format_exports(shell: Shell, vars: &[(&str, String)]) -> String {
+    vars.iter()
+        .map(|(k, v)| match shell {
+            Shell::Bash | Shell::Zsh => format!("export {}={}", k, quote(shell, v)),
+            Shell::Fish => format!("set -gx {} {}", k, quote(shell, v)),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Returns the statements that unset environment variables in a shell
+pub fn format_unsets(shell: Shell, names: &[&str]) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("unset {}", names.join(" ")),
+        Shell::Fish => format!("set -e {}", names.join(" ")),
+    }
+}
+
+/// Quotes a value so that the shell reads it literally
+pub fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', r"'\''")),
+        Shell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_exports() {
+        let vars = vec![("AWS_CREDS_PROFILE", "it's".to_string()), ("AWS_REGION", r"a\b".to_string())];
+        assert_eq!(format_exports(Shell::Bash, &vars), "export AWS_CREDS_PROFILE='it'\\''s'\nexport AWS_REGION='a\\b'");
+        assert_eq!(format_exports(Shell::Fish, &vars), "set -gx AWS_CREDS_PROFILE 'it\\'s'\nset -gx AWS_REGION 'a\\\\b'");
+    }
+}
\ No newline at end of file
@@ -1,18 +1,138 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
+use crate::utils::get_mfa_device_label;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CredentialsProfile {
     pub profile_name: String,
     pub permanent_credentials: PermanentCredentials,
     pub temporary_credentials: TemporaryCredentials,
+
+    /// Free-form labels used to select groups of profiles (e.g. `staging`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Overrides `AwsProfiles.refresh_threshold` for this profile (e.g. `15m`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_threshold: Option<String>,
+
+    /// When the profile was last used (RFC 3339), which orders the interactive picker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<String>,
+
+    /// Set for profiles that assume a role with an OIDC token instead of using an access key, in
+    /// which case the permanent access key pair is left empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_identity: Option<WebIdentity>,
+
+    /// The ARN of the identity behind the profile's credentials, recorded in the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller_arn: Option<String>,
 }
 
+/// Read and written as `PermanentCredentialsFile`, which keeps config files readable by older versions
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "PermanentCredentialsFile", into = "PermanentCredentialsFile")]
 pub struct PermanentCredentials {
     pub access_key_id: String,
     pub secret_access_key: String,
-    pub mfa_serial_number: String,
     pub region: String,
+    pub mfa_devices: Vec<MfaDevice>,
+
+    /// The label of the MFA device that was used last
+    pub last_mfa_device: Option<String>,
+
+    /// When the access key was created (RFC 3339), used to flag keys that are due for rotation
+    pub access_key_created: Option<String>,
+}
+
+/// How `PermanentCredentials` are stored. Older versions only know one MFA device and require
+/// `mfa_serial_number`, so the first device is also written there.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PermanentCredentialsFile {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub region: String,
+
+    #[serde(default)]
+    pub mfa_serial_number: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mfa_totp_seed: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mfa_devices: Vec<MfaDevice>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_mfa_device: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_created: Option<String>,
+}
+
+impl From<PermanentCredentialsFile> for PermanentCredentials {
+    fn from(file: PermanentCredentialsFile) -> PermanentCredentials {
+        // Config files written by older versions (or rewritten by them, which drops `mfa_devices`)
+        // only have the one device
+        let mut mfa_devices = file.mfa_devices;
+        if mfa_devices.is_empty() && !file.mfa_serial_number.is_empty() {
+            mfa_devices.push(MfaDevice {
+                label: get_mfa_device_label(&file.mfa_serial_number),
+                serial_number: file.mfa_serial_number,
+                totp_seed: file.mfa_totp_seed,
+            });
+        }
+
+        PermanentCredentials {
+            access_key_id: file.access_key_id,
+            secret_access_key: file.secret_access_key,
+            region: file.region,
+            mfa_devices,
+            last_mfa_device: file.last_mfa_device,
+            access_key_created: file.access_key_created,
+        }
+    }
+}
+
+impl From<PermanentCredentials> for PermanentCredentialsFile {
+    fn from(creds: PermanentCredentials) -> PermanentCredentialsFile {
+        let first_device = creds.mfa_devices.first().cloned();
+        PermanentCredentialsFile {
+            access_key_id: creds.access_key_id,
+            secret_access_key: creds.secret_access_key,
+            region: creds.region,
+            mfa_serial_number: first_device.as_ref().map(|d| d.serial_number.to_owned()).unwrap_or_default(),
+            mfa_totp_seed: first_device.and_then(|d| d.totp_seed),
+            mfa_devices: creds.mfa_devices,
+            last_mfa_device: creds.last_mfa_device,
+            access_key_created: creds.access_key_created,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MfaDevice {
+    pub label: String,
+    pub serial_number: String,
+
+    /// The base32 seed of a virtual MFA device, when aws-creds generates the MFA codes itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_seed: Option<String>,
+}
+
+/// The role to assume with `AssumeRoleWithWebIdentity`, and where to read the OIDC token from.
+/// Exactly one of `token_file` and `token_command` is set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebIdentity {
+    pub role_arn: String,
+    pub role_session_name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<String>,
+
+    /// A shell command that prints the token to stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_command: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -27,6 +147,82 @@ pub struct TemporaryCredentials {
 pub struct AwsProfiles{
     pub default: String,
     pub profiles: Vec<CredentialsProfile>,
+
+    /// How long before expiration temporary credentials are considered to be expiring soon (e.g. `15m`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_threshold: Option<String>,
+
+    /// Whether commands run on a terminal without `--profile` open a profile picker instead of using `default`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interactive_picker: bool,
+
+    /// Which profile the git credential helper uses for each CodeCommit host
+    /// (e.g. `git-codecommit.eu-west-1.amazonaws.com`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub git_hosts: BTreeMap<String, String>,
+
+    /// Which profile the Docker credential helper uses for each ECR registry
+    /// (e.g. `123456789012.dkr.ecr.eu-west-1.amazonaws.com`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub docker_registries: BTreeMap<String, String>,
+
+    /// Stops commands from appending to the audit log next to the config file
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_audit_log: bool,
+}
+
+/// The cached ECR authorization tokens, keyed by registry
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EcrTokenCache {
+    pub tokens: BTreeMap<String, EcrToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EcrToken {
+    /// The profile the token was retrieved with, so that changing the registry's profile ignores it
+    pub profile_name: String,
+    pub password: String,
+    pub expiration: String,
+}
+
+/// One line of the audit log. Secrets are never recorded, only which profile was used, by whom and
+/// until when.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEvent {
+    /// When the event happened (RFC 3339)
+    pub timestamp: String,
+
+    /// The command that caused the event (e.g. `get`)
+    pub event: String,
+    pub profile: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller_arn: Option<String>,
+
+    /// When the profile's session expires (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<String>,
+
+    /// The profile's new name, for `rename` events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+
+    /// The command line of the aws-creds invocation, without the command passed to `exec`
+    pub command: Vec<String>,
+}
+
+/// A project file (`.aws-creds.json`) that selects the profile for every directory below it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectFile {
+    pub profile: String,
+
+    /// Path to the config file, relative to the project file's directory unless absolute
+    #[serde(default)]
+    pub config: Option<String>,
+
+    /// Overrides the profile's region
+    #[serde(default)]
+    pub region: Option<String>,
 }
 
 // This custom type makes it possible to handle file not found errors with more precision
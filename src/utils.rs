@@ -1,36 +1,101 @@
-use crate::types::{AwsProfiles, FileError};
+use crate::types::{AwsProfiles, CredentialsProfile, FileError, PermanentCredentials, ProjectFile};
 use anyhow::{Error, anyhow};
-use std::fs;
+use std::{fs, io::{IsTerminal, Write}, path::{Path, PathBuf}};
 
-pub fn get_default_config_path() -> Result<String, Error> {
-    let home_dir = dirs::home_dir().expect("Failed to get user's home directory");
-    let result = home_dir
-        .join(".config")
+/// The name of the file that selects a profile for a directory tree
+pub const PROJECT_FILE_NAME: &str = ".aws-creds.json";
+
+/// Where the config path came from
+#[derive(PartialEq, Eq, Debug)]
+pub enum ConfigPathSource {
+    Argument,
+    ProjectFile(PathBuf),
+    Environment,
+    XdgConfigHome,
+    HomeDirectory,
+}
+
+impl std::fmt::Display for ConfigPathSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigPathSource::Argument => write!(f, "the --config argument"),
+            ConfigPathSource::ProjectFile(p) => write!(f, "the project file `{}`", p.display()),
+            ConfigPathSource::Environment => write!(f, "the AWS_CREDS_CONFIG environment variable"),
+            ConfigPathSource::XdgConfigHome => write!(f, "the XDG_CONFIG_HOME environment variable"),
+            ConfigPathSource::HomeDirectory => write!(f, "the home directory"),
+        }
+    }
+}
+
+/// Returns the default config path, looking at AWS_CREDS_CONFIG, then XDG_CONFIG_HOME, then the
+/// home directory
+pub fn get_default_config_path_with_source() -> Result<(String, ConfigPathSource), Error> {
+    default_config_path_from(
+        std::env::var("AWS_CREDS_CONFIG").ok(),
+        std::env::var("XDG_CONFIG_HOME").ok(),
+        dirs::home_dir(),
+    )
+}
+
+fn default_config_path_from(
+    aws_creds_config: Option<String>,
+    xdg_config_home: Option<String>,
+    home_dir: Option<PathBuf>,
+) -> Result<(String, ConfigPathSource), Error> {
+    if let Some(p) = aws_creds_config.filter(|p| !p.is_empty()) {
+        return Ok((p, ConfigPathSource::Environment));
+    }
+
+    // The XDG spec says that relative paths should be ignored
+    let (config_dir, source) = match xdg_config_home.map(PathBuf::from).filter(|p| p.is_absolute()) {
+        Some(p) => (p, ConfigPathSource::XdgConfigHome),
+        None => {
+            let home_dir = home_dir.ok_or_else(|| anyhow!("failed to find the home directory; please pass --config or set AWS_CREDS_CONFIG"))?;
+            (home_dir.join(".config"), ConfigPathSource::HomeDirectory)
+        },
+    };
+
+    let result = config_dir
         .join("aws-creds")
         .join("creds.json");
 
-    Ok(result.to_str().expect("Failed to create path to creds.json").to_string())
+    match result.to_str() {
+        Some(p) => Ok((p.to_string(), source)),
+        None => Err(anyhow!("the path `{}` is not valid UTF-8", result.display())),
+    }
+}
+
+/// Returns the config path and where it came from: the --config argument, then the project file,
+/// then the default config path
+pub fn resolve_config_path(config_path: &Option<String>) -> Result<(String, ConfigPathSource), Error> {
+    if let Some(p) = config_path {
+        return Ok((p.to_owned(), ConfigPathSource::Argument));
+    }
+
+    if let Some((project_path, project)) = find_project_file()? {
+        if let Some(c) = project.config {
+            return Ok((resolve_project_path(&project_path, &c), ConfigPathSource::ProjectFile(project_path)));
+        }
+    }
+
+    get_default_config_path_with_source()
 }
 
-/// If the config_path is empty, return the default config path; otherwise, verify that the config_path exists.
+/// If the config_path is empty, return the config path of the project file or else the default config
+/// path; otherwise, verify that the config_path exists.
 pub fn check_config_path(config_path: &Option<String>) -> Result<String, FileError> {
-      let path = match config_path {
-        Some(p) => p,
-        _ => {
-            match get_default_config_path() {
-                Ok(p) => &p.to_owned(),
-                Err(e) => return Err(FileError::Other { message: e.to_string() }),
-            }
-        },
+    let (path, _) = match resolve_config_path(config_path) {
+        Ok(p) => p,
+        Err(e) => return Err(FileError::Other { message: e.to_string() }),
     };
 
     // Make sure that the file exists
-    match fs::exists(path) {
+    match fs::exists(&path) {
         Ok(exists) => {
             if !exists {
                 Err(FileError::NotFound)
             } else {
-                Ok(path.to_owned())
+                Ok(path)
             }
         },
         Err(e) => {
@@ -60,10 +125,103 @@ pub fn parse_creds(config_path: &String) -> Result<AwsProfiles, FileError> {
     Ok(result)
 }
 
-pub fn write_creds(creds: &AwsProfiles, config_path: &String) -> Result<(), Error> {
-    let contents = serde_json::to_string(creds)?;
-    fs::write(config_path, contents)?;
-    Ok(())
+/// Returns a label for an MFA device, which is the device name for virtual devices (i.e. `phone` in
+/// `arn:aws:iam::123456789012:mfa/phone`) and the serial number for hardware devices
+pub fn get_mfa_device_label(serial_number: &str) -> String {
+    match serial_number.rsplit_once(":mfa/") {
+        Some((_, name)) => name.rsplit('/').next().unwrap_or(name).to_string(),
+        None => serial_number.to_string(),
+    }
+}
+
+pub fn write_creds(creds: &AwsProfiles, config_path: &str) -> Result<(), Error> {
+    write_private_file(config_path, &serde_json::to_string(creds)?)
+}
+
+/// Writes a file that only the user can read
+pub fn write_private_file(path: &str, contents: &str) -> Result<(), Error> {
+    // Write through symlinks (e.g. from dotfile managers) rather than replacing them with a file
+    let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+    // Write to a temporary file and move it over the file, so that a failure can never leave the
+    // file half-written. Its name is unique, so that concurrent runs don't trip over each other.
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", random_suffix()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut f| {
+            f.write_all(contents.as_bytes())?;
+            f.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, &path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    Ok(result?)
+}
+
+/// Returns a random suffix for the names of temporary files, so that other users can't predict them
+pub fn random_suffix() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    // RandomState is seeded from the OS's random number generator
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:016x}", hasher.finish())
+}
+
+/// Groups profiles by IAM user (i.e. access key), keeping the order in which they were passed, so that
+/// one session can be retrieved for each group. Profiles of the same user may list different MFA
+/// devices, and any of them will do, so each group gets all of them.
+pub fn group_profiles_by_user(profiles: Vec<(String, PermanentCredentials)>) -> Vec<(PermanentCredentials, Vec<String>)> {
+    let mut groups: Vec<(PermanentCredentials, Vec<String>)> = Vec::new();
+    for (name, perm_creds) in profiles {
+        match groups.iter_mut().find(|(c, _)| c.access_key_id == perm_creds.access_key_id) {
+            Some((c, group_names)) => {
+                for d in perm_creds.mfa_devices {
+                    if !c.mfa_devices.iter().any(|e| e.serial_number == d.serial_number) {
+                        c.mfa_devices.push(d);
+                    }
+                }
+
+                group_names.push(name);
+            },
+            None => groups.push((perm_creds, vec![name])),
+        }
+    }
+
+    groups
+}
+
+/// Inserts a profile, replacing any existing profiles with the same name
+pub fn upsert_profile(all_creds: &mut AwsProfiles, profile: CredentialsProfile) {
+    match all_creds.profiles.iter().position(|p| p.profile_name == profile.profile_name) {
+        Some(i) => {
+            let name = profile.profile_name.to_owned();
+            all_creds.profiles[i] = profile;
+
+            // Drop the duplicates that older versions could create when overwriting a profile
+            let mut index = 0;
+            all_creds.profiles.retain(|p| {
+                let keep = index <= i || p.profile_name != name;
+                index += 1;
+                keep
+            });
+        },
+        None => all_creds.profiles.push(profile),
+    }
 }
 
 /// Returns the default profile name if no profile_name is passed; otherwise, makes sure that
@@ -81,67 +239,514 @@ pub fn parse_profile_name(profile_name: &Option<String>, default_profile: &str)
     }
 }
 
+/// Returns the names of the profiles that have every one of the tags (or of every profile if no tags
+/// are passed)
+pub fn select_profiles_by_tags(all_creds: &AwsProfiles, tags: &[String]) -> Result<Vec<String>, Error> {
+    let names: Vec<String> = all_creds.profiles
+        .iter()
+        .filter(|p| tags.iter().all(|t| p.tags.contains(t)))
+        .map(|p| p.profile_name.to_owned())
+        .collect();
+
+    if names.is_empty() && !tags.is_empty() {
+        return Err(anyhow!("no profiles are tagged with {}", tags.iter().map(|t| format!("`{}`", t)).collect::<Vec<String>>().join(", ")));
+    }
+
+    Ok(names)
+}
+
+/// How old an access key can get before it should be rotated
+pub const MAX_ACCESS_KEY_AGE_DAYS: i64 = 90;
+
+/// Returns whether the access key is older than MAX_ACCESS_KEY_AGE_DAYS (false if its age is unknown)
+pub fn is_access_key_overdue(access_key_created: &Option<String>) -> Result<bool, Error> {
+    match access_key_created {
+        Some(c) => {
+            let created = chrono::DateTime::parse_from_rfc3339(c)
+                .map_err(|e| anyhow!("invalid access_key_created `{}`: {}", c, e))?
+                .with_timezone(&chrono::Utc);
+            Ok(chrono::Utc::now() - created > chrono::Duration::days(MAX_ACCESS_KEY_AGE_DAYS))
+        },
+        None => Ok(false),
+    }
+}
+
+/// The refresh threshold used when neither the profile nor the config file sets one
+pub const DEFAULT_REFRESH_THRESHOLD: &str = "5m";
+
+/// Searches the current directory and its parents for a project file, returning its path and contents
+pub fn find_project_file() -> Result<Option<(PathBuf, ProjectFile)>, Error> {
+    find_project_file_from(&std::env::current_dir()?)
+}
+
+fn find_project_file_from(dir: &Path) -> Result<Option<(PathBuf, ProjectFile)>, Error> {
+    for d in dir.ancestors() {
+        let path = d.join(PROJECT_FILE_NAME);
+        if path.is_file() {
+            let contents = fs::read_to_string(&path)?;
+            let project: ProjectFile = serde_json::from_str(&contents)
+                .map_err(|e| anyhow!("failed to parse project file `{}`: {}", path.display(), e))?;
+
+            return Ok(Some((path, project)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolves a path from a project file relative to the project file's directory
+fn resolve_project_path(project_path: &Path, path: &str) -> String {
+    match project_path.parent() {
+        Some(dir) => dir.join(path).to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Returns the region set by the project file, if the project file selects the profile
+pub fn get_project_region(profile_name: &str) -> Result<Option<String>, Error> {
+    match find_project_file()? {
+        Some((_, project)) if project.profile == profile_name => Ok(project.region),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the region of a profile, unless the project file overrides it
+pub fn get_profile_region(all_creds: &AwsProfiles, profile_name: &str) -> Result<String, Error> {
+    if let Some(region) = get_project_region(profile_name)? {
+        return Ok(region);
+    }
+
+    match all_creds.profiles.iter().find(|p| p.profile_name == profile_name) {
+        Some(p) => Ok(p.permanent_credentials.region.to_owned()),
+        None => Err(anyhow!("profile `{}` doesn't exist", profile_name)),
+    }
+}
+
+/// Returns the profile passed on the command line. If none was passed, the profile named by the
+/// project file is used, and after that, the user picks one from a list when `interactive_picker` is
+/// enabled and the command runs on a terminal; otherwise, the default profile is used
+pub fn resolve_profile_name(profile_name: &Option<String>, all_creds: &AwsProfiles) -> Result<String, Error> {
+    if profile_name.is_none() {
+        if let Some((project_path, project)) = find_project_file()? {
+            let name = parse_profile_name(&Some(project.profile), &all_creds.default)?;
+            eprintln!("Using profile `{}` from `{}`", name, project_path.display());
+            return Ok(name);
+        }
+    }
+
+    if profile_name.is_none()
+        && all_creds.interactive_picker
+        && std::io::stdin().is_terminal()
+        && std::io::stderr().is_terminal() {
+        return pick_profile(all_creds);
+    }
+
+    parse_profile_name(profile_name, &all_creds.default)
+}
+
+/// Opens a searchable list of the profiles, with the most recently used ones first
+fn pick_profile(all_creds: &AwsProfiles) -> Result<String, Error> {
+    let mut profiles: Vec<&CredentialsProfile> = all_creds.profiles.iter().collect();
+    profiles.sort_by(|a, b| {
+        b.last_used.cmp(&a.last_used)
+            .then_with(|| a.profile_name.to_lowercase().cmp(&b.profile_name.to_lowercase()))
+    });
+
+    let mut options: Vec<String> = Vec::new();
+    for p in profiles.iter() {
+        let mut annotations: Vec<&str> = Vec::new();
+        if p.profile_name == all_creds.default {
+            annotations.push("default");
+        }
+
+        match get_temp_cred_status(&p.temporary_credentials.expiration, get_refresh_threshold(all_creds, p)?)? {
+            TempCredStatus::Empty => annotations.push("empty"),
+            TempCredStatus::Expired => annotations.push("expired"),
+            TempCredStatus::ExpiringSoon => annotations.push("expiring soon"),
+            TempCredStatus::Ok => {},
+        }
+
+        let account = p.permanent_credentials.mfa_devices
+            .iter()
+            .find_map(|d| get_account_id(&d.serial_number))
+            .unwrap_or("unknown account");
+        if annotations.is_empty() {
+            options.push(format!("{} ({})", p.profile_name, account));
+        } else {
+            options.push(format!("{} ({}) <- {}", p.profile_name, account, annotations.join(", ")));
+        }
+    }
+
+    let selection = inquire::Select::new("Profile:", options)
+        .raw_prompt()
+        .map_err(|_| anyhow!("failed to get user selection for profile"))?;
+
+    Ok(profiles[selection.index].profile_name.to_owned())
+}
+
+/// Records that a profile was just used, so that the interactive picker lists it first
+pub fn mark_profile_used(all_creds: &mut AwsProfiles, profile_name: &str) {
+    for p in all_creds.profiles.iter_mut() {
+        if p.profile_name == profile_name {
+            p.last_used = Some(chrono::Utc::now().to_rfc3339());
+        }
+    }
+}
+
+/// Returns the account ID of an ARN (i.e. `123456789012` in `arn:aws:iam::123456789012:mfa/user`)
+pub fn get_account_id(arn: &str) -> Option<&str> {
+    let parts: Vec<&str> = arn.split(':').collect();
+    if parts.len() < 6 || parts[0] != "arn" || parts[4].is_empty() {
+        return None;
+    }
+
+    Some(parts[4])
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum TempCredStatus {
     Empty,
     Expired,
+    ExpiringSoon,
     Ok,
 }
 
-/// Validates whether the temporary credentials are empty, ok, or expired
-pub fn get_temp_cred_status(expiration_timestamp: &str) -> Result<TempCredStatus, Error> {
-    let now = chrono::Utc::now();
+/// Parses a duration such as `90s`, `15m`, `1h` or `2d`
+pub fn parse_duration(input: &str) -> Result<chrono::Duration, Error> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration `{}` (expected a value like `90s`, `15m`, `1h` or `2d`)", input))?;
+
+    // The constructors return None for durations too long to represent
+    let duration = match unit {
+        "s" => chrono::TimeDelta::try_seconds(value),
+        "m" => chrono::TimeDelta::try_minutes(value),
+        "h" => chrono::TimeDelta::try_hours(value),
+        "d" => chrono::TimeDelta::try_days(value),
+        _ => None,
+    };
+
+    duration.ok_or_else(|| anyhow!("invalid duration `{}` (expected a value like `90s`, `15m`, `1h` or `2d`)", input))
+}
+
+/// Formats a duration compactly (i.e. `2h13m`, `45m` or `30s`)
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes) = (seconds / 3600, (seconds % 3600) / 60);
+
+    if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Returns the refresh threshold of a profile, falling back to the global threshold and then to
+/// DEFAULT_REFRESH_THRESHOLD
+pub fn get_refresh_threshold(all_creds: &AwsProfiles, profile: &CredentialsProfile) -> Result<chrono::Duration, Error> {
+    let threshold = profile.refresh_threshold.as_deref()
+        .or(all_creds.refresh_threshold.as_deref())
+        .unwrap_or(DEFAULT_REFRESH_THRESHOLD);
+
+    parse_duration(threshold)
+}
+
+/// Returns how long the temporary credentials have left before they expire, or None if they are empty
+pub fn get_remaining_lifetime(expiration_timestamp: &str) -> Result<Option<chrono::Duration>, Error> {
     let cred_expiration = match chrono::DateTime::parse_from_rfc3339(expiration_timestamp)
         .map(|dt| dt.with_timezone(&chrono::Utc)) {
             Ok(t) => t,
             Err(e) => {
                 // If the timestamp arg == ""...
                 if e.kind() == chrono::format::ParseErrorKind::TooShort {
-                    return Ok(TempCredStatus::Empty)
+                    return Ok(None)
                 } else {
                     return Err(e.into());
                 }
             }
         };
 
-    if cred_expiration < now {
-        return Ok(TempCredStatus::Expired);
+    Ok(Some(cred_expiration - chrono::Utc::now()))
+}
+
+/// Validates whether the temporary credentials are empty, ok, expired, or will expire within `threshold`
+pub fn get_temp_cred_status(expiration_timestamp: &str, threshold: chrono::Duration) -> Result<TempCredStatus, Error> {
+    let remaining = match get_remaining_lifetime(expiration_timestamp)? {
+        Some(r) => r,
+        None => return Ok(TempCredStatus::Empty),
+    };
+
+    if remaining < chrono::Duration::zero() {
+        Ok(TempCredStatus::Expired)
+    } else if remaining < threshold {
+        Ok(TempCredStatus::ExpiringSoon)
     } else {
-        return Ok(TempCredStatus::Ok);
+        Ok(TempCredStatus::Ok)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::MfaDevice;
     use core::panic;
 
     #[test]
     fn test_get_temp_cred_status () {
         let expired_timestamp = "2025-03-13T10:57:34Z";
+        let threshold = chrono::Duration::minutes(5);
         let current_time = chrono::Utc::now() + chrono::Duration::hours(1);
         let current_timestamp = current_time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let expiring_time = chrono::Utc::now() + chrono::Duration::minutes(2);
+        let expiring_timestamp = expiring_time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+
+        let exp_output = match get_temp_cred_status(expired_timestamp, threshold){
+            Ok(v) => v,
+            Err(e) => panic!("{}", e.to_string()),
+        };
 
-        let exp_output = match get_temp_cred_status(expired_timestamp){
+        let curr_output = match get_temp_cred_status(&current_timestamp, threshold) {
             Ok(v) => v,
             Err(e) => panic!("{}", e.to_string()),
         };
 
-        let curr_output = match get_temp_cred_status(&current_timestamp) {
+        let expiring_output = match get_temp_cred_status(&expiring_timestamp, threshold) {
             Ok(v) => v,
             Err(e) => panic!("{}", e.to_string()),
         };
 
-        let empty_output = match get_temp_cred_status("") {
+        let empty_output = match get_temp_cred_status("", threshold) {
             Ok(v) => v,
             Err(e) => panic!("{}", e.to_string()),
         };
 
         assert_eq!(exp_output, TempCredStatus::Expired);
         assert_eq!(curr_output, TempCredStatus::Ok);
+        assert_eq!(expiring_output, TempCredStatus::ExpiringSoon);
         assert_eq!(empty_output, TempCredStatus::Empty);
     }
 
+    #[test]
+    fn test_select_profiles_by_tags() {
+        let path = std::env::current_dir().expect("failed to retrieve working directory").join("test_assets").join("creds.json");
+        let all_creds = match parse_creds(&path.to_str().expect("failed to parse path to creds file as string").to_string()) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e.to_string()),
+        };
+
+        assert_eq!(select_profiles_by_tags(&all_creds, &["staging".to_string()]).unwrap(), vec!["test2", "test1"]);
+        assert_eq!(select_profiles_by_tags(&all_creds, &["staging".to_string(), "eu".to_string()]).unwrap(), vec!["test2"]);
+
+        match select_profiles_by_tags(&all_creds, &["prod".to_string()]) {
+            Ok(_) => panic!("ERROR: select_profiles_by_tags matched a tag that no profile has"),
+            Err(e) => assert_eq!(e.to_string(), "no profiles are tagged with `prod`"),
+        };
+    }
+
+    #[test]
+    fn test_get_account_id() {
+        assert_eq!(get_account_id("arn:aws:iam::123456789012:mfa/user"), Some("123456789012"));
+        assert_eq!(get_account_id("arn:aws:sts::123456789012:assumed-role/role/session"), Some("123456789012"));
+        assert_eq!(get_account_id("not_used"), None);
+        assert_eq!(get_account_id(""), None);
+    }
+
+    #[test]
+    fn test_find_project_file() {
+        let root = std::env::temp_dir().join(format!("aws-creds-test-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).expect("failed to create test directories");
+
+        match find_project_file_from(&nested) {
+            Ok(None) => {},
+            Ok(Some((p, _))) => panic!("found unexpected project file `{}`", p.display()),
+            Err(e) => panic!("{}", e),
+        };
+
+        fs::write(root.join(PROJECT_FILE_NAME), r#"{"profile": "prod", "config": "creds.json"}"#)
+            .expect("failed to write project file");
+
+        match find_project_file_from(&nested) {
+            Ok(Some((p, project))) => {
+                assert_eq!(p, root.join(PROJECT_FILE_NAME));
+                assert_eq!(project.profile, "prod");
+                assert_eq!(project.region, None);
+                assert_eq!(
+                    resolve_project_path(&p, &project.config.expect("missing config")),
+                    root.join("creds.json").to_string_lossy(),
+                );
+            },
+            Ok(None) => panic!("failed to find project file"),
+            Err(e) => panic!("{}", e),
+        };
+
+        fs::remove_dir_all(&root).expect("failed to remove test directories");
+    }
+
+    #[test]
+    fn test_default_config_path_from() {
+        let home = Some(PathBuf::from("/home/user"));
+
+        assert_eq!(
+            default_config_path_from(Some("/etc/creds.json".to_string()), Some("/xdg".to_string()), home.clone()).unwrap(),
+            ("/etc/creds.json".to_string(), ConfigPathSource::Environment),
+        );
+        assert_eq!(
+            default_config_path_from(Some("".to_string()), Some("/xdg".to_string()), home.clone()).unwrap(),
+            ("/xdg/aws-creds/creds.json".to_string(), ConfigPathSource::XdgConfigHome),
+        );
+        assert_eq!(
+            default_config_path_from(None, Some("relative".to_string()), home.clone()).unwrap(),
+            ("/home/user/.config/aws-creds/creds.json".to_string(), ConfigPathSource::HomeDirectory),
+        );
+        assert!(default_config_path_from(None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_upsert_profile() {
+        let path = std::env::current_dir().expect("failed to retrieve working directory").join("test_assets").join("creds.json");
+        let mut all_creds = match parse_creds(&path.to_str().expect("failed to parse path to creds file as string").to_string()) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e.to_string()),
+        };
+
+        let mut profile = all_creds.profiles[0].clone();
+        profile.permanent_credentials.region = "eu-west-1".to_string();
+        all_creds.profiles.push(all_creds.profiles[0].clone());
+        upsert_profile(&mut all_creds, profile.clone());
+
+        assert_eq!(all_creds.profiles.len(), 2);
+        assert_eq!(all_creds.profiles[0].profile_name, "test2");
+        assert_eq!(all_creds.profiles[0].permanent_credentials.region, "eu-west-1");
+
+        profile.profile_name = "test3".to_string();
+        upsert_profile(&mut all_creds, profile);
+        assert_eq!(all_creds.profiles.len(), 3);
+        assert_eq!(all_creds.profiles[2].profile_name, "test3");
+    }
+
+    #[test]
+    fn test_parse_creds_migrates_mfa_serial_number() {
+        let path = std::env::current_dir().expect("failed to retrieve working directory").join("test_assets").join("creds.json");
+        let all_creds = match parse_creds(&path.to_str().expect("failed to parse path to creds file as string").to_string()) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e.to_string()),
+        };
+
+        let perm_creds = &all_creds.profiles[0].permanent_credentials;
+        assert_eq!(perm_creds.mfa_devices, vec![MfaDevice {
+            label: "not_used".to_string(),
+            serial_number: "not_used".to_string(),
+            totp_seed: None,
+        }]);
+
+        // The first device is still written for older versions, and isn't migrated a second time
+        let mut serialized = serde_json::to_value(&all_creds).expect("failed to serialize config");
+        assert_eq!(serialized["profiles"][0]["permanent_credentials"]["mfa_serial_number"], "not_used");
+
+        let perm_creds = &mut serialized["profiles"][0]["permanent_credentials"];
+        perm_creds["mfa_devices"].as_array_mut().unwrap().push(serde_json::json!({
+            "label": "backup",
+            "serial_number": "arn:aws:iam::123456789012:mfa/backup",
+        }));
+
+        let reparsed: AwsProfiles = serde_json::from_value(serialized).expect("failed to parse config");
+        assert_eq!(reparsed.profiles[0].permanent_credentials.mfa_devices.len(), 2);
+
+        // Older versions drop `mfa_devices` when they rewrite the file, leaving only the serial number
+        let old: AwsProfiles = serde_json::from_str(r#"{"default":"a","profiles":[{"profile_name":"a","permanent_credentials":{"access_key_id":"x","secret_access_key":"y","region":"eu-west-1","mfa_serial_number":"arn:aws:iam::123456789012:mfa/phone"},"temporary_credentials":{"access_key_id":"","secret_access_key":"","session_token":"","expiration":""}}]}"#)
+            .expect("failed to parse config");
+        assert_eq!(old.profiles[0].permanent_credentials.mfa_devices[0].label, "phone");
+    }
+
+    #[test]
+    fn test_group_profiles_by_user() {
+        let device = |label: &str| MfaDevice {
+            label: label.to_string(),
+            serial_number: format!("arn:aws:iam::123456789012:mfa/{}", label),
+            totp_seed: None,
+        };
+        let perm_creds = |access_key_id: &str, mfa_devices: Vec<MfaDevice>| PermanentCredentials {
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: "secret".to_string(),
+            region: "eu-west-1".to_string(),
+            mfa_devices,
+            last_mfa_device: None,
+            access_key_created: None,
+        };
+
+        let groups = group_profiles_by_user(vec![
+            ("prod".to_string(), perm_creds("AKIAALICE", vec![device("phone")])),
+            ("ci".to_string(), perm_creds("AKIACI", vec![])),
+            ("staging".to_string(), perm_creds("AKIAALICE", vec![device("phone"), device("yubikey")])),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].1, vec!["prod", "staging"]);
+        assert_eq!(groups[0].0.mfa_devices, vec![device("phone"), device("yubikey")]);
+        assert_eq!(groups[1].1, vec!["ci"]);
+        assert!(groups[1].0.mfa_devices.is_empty());
+        assert!(group_profiles_by_user(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_get_mfa_device_label() {
+        assert_eq!(get_mfa_device_label("arn:aws:iam::123456789012:mfa/phone"), "phone");
+        assert_eq!(get_mfa_device_label("arn:aws:iam::123456789012:mfa/path/backup"), "backup");
+        assert_eq!(get_mfa_device_label("GAHT12345678"), "GAHT12345678");
+    }
+
+    #[test]
+    fn test_write_private_file() {
+        let dir = std::env::temp_dir().join(format!("aws-creds-test-{}", random_suffix()));
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("creds.json");
+        let path_str = path.to_str().unwrap();
+
+        write_private_file(path_str, "first").unwrap();
+        write_private_file(path_str, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+
+        // Symlinks are written through, and no temporary files are left behind
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.json");
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            write_private_file(link.to_str().unwrap(), "third").unwrap();
+            assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "third");
+        }
+
+        assert_eq!(fs::read_dir(&dir).unwrap().filter(|e| e.as_ref().unwrap().path().to_string_lossy().ends_with(".tmp")).count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), chrono::Duration::seconds(90));
+        assert_eq!(parse_duration("15m").unwrap(), chrono::Duration::minutes(15));
+        assert_eq!(parse_duration("1h").unwrap(), chrono::Duration::hours(1));
+        assert_eq!(parse_duration("2d").unwrap(), chrono::Duration::days(2));
+        assert!(parse_duration("15").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("99999999999999d").is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::minutes(133)), "2h13m");
+        assert_eq!(format_duration(chrono::Duration::minutes(45)), "45m");
+        assert_eq!(format_duration(chrono::Duration::seconds(30)), "30s");
+        assert_eq!(format_duration(chrono::Duration::seconds(-30)), "0s");
+    }
+
     #[test]
     fn test_parse_profile_name() {
         match parse_profile_name(&None, "test1") {